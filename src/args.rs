@@ -1,27 +1,392 @@
-use clap::Parser;
+use crate::file_utils::language::Language;
+use crate::output::formatter::OutputFormat;
+use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    #[arg(short, long)]
-    pub path: PathBuf,
+    #[arg(
+        short,
+        long,
+        required_unless_present = "files_from",
+        help = "Directory to snapshot; repeat to combine several roots (git diff mode takes one)"
+    )]
+    pub path: Vec<PathBuf>,
 
-    #[arg(short, long)]
+    #[arg(long)]
     pub pattern: Option<String>,
 
+    #[arg(short, long, help = "Match --pattern case-insensitively")]
+    pub ignore_case: bool,
+
+    #[arg(
+        long,
+        requires = "pattern",
+        conflicts_with_all = ["git_from", "git_to", "commit", "since", "diff_path"],
+        help = "Print only the files that --pattern never matches"
+    )]
+    pub invert_match: bool,
+
+    #[arg(
+        short = 'l',
+        long,
+        requires = "pattern",
+        conflicts_with_all = ["git_from", "git_to", "commit", "since", "diff_path"],
+        help = "Print only the paths of files that --pattern matches (or, with --invert-match, does not match)"
+    )]
+    pub files_with_matches: bool,
+
     #[arg(short, long, value_delimiter = ',')]
     pub extensions: Option<Vec<String>>,
 
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Only include files written in these languages (adds their extensions to --extensions)"
+    )]
+    pub languages: Option<Vec<Language>>,
+
     #[arg(short, long, default_value = "3")]
     pub context_lines: usize,
 
     #[arg(long, help = "Git revision (tag, branch, or commit) to diff from")]
     pub git_from: Option<String>,
 
-    #[arg(long, help = "Git revision (tag, branch, or commit) to diff to")]
+    #[arg(
+        long,
+        help = "Git revision (tag, branch, or commit) to diff to, or WORKTREE for uncommitted changes"
+    )]
     pub git_to: Option<String>,
 
-    #[arg(long, value_delimiter = ',', help = "Patterns to exclude from the results")]
+    #[arg(
+        long,
+        conflicts_with_all = ["git_from", "git_to"],
+        help = "Show the changes made by a single commit, i.e. diff <COMMIT>^ to <COMMIT>"
+    )]
+    pub commit: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "REV",
+        conflicts_with_all = ["git_from", "git_to", "commit"],
+        help = "Show everything that changed since REV, i.e. diff <REV> to HEAD"
+    )]
+    pub since: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["git_from", "git_to", "commit", "since", "stats_only", "dry_run", "toc"],
+        help = "Diff the files under --path against the same relative paths under DIR"
+    )]
+    pub diff_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "In git diff mode, also show untracked files (except ignored ones) as additions"
+    )]
+    pub include_untracked: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Patterns to exclude from the results"
+    )]
     pub excludes: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Plain,
+        help = "Output format for the file listing"
+    )]
+    pub format: OutputFormat,
+
+    #[arg(short, long, help = "Write the output to this file instead of stdout")]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        requires = "output",
+        help = "Gzip-compress the file written by --output"
+    )]
+    pub gzip: bool,
+
+    #[arg(
+        long,
+        value_parser = parse_size,
+        help = "Skip files larger than this size (e.g. 4096, 500k, 2M)"
+    )]
+    pub max_file_size: Option<u64>,
+
+    #[arg(long, help = "Include files that are ignored by .gitignore")]
+    pub no_gitignore: bool,
+
+    #[arg(
+        long,
+        help = "Skip hidden files and directories (they are included by default)"
+    )]
+    pub skip_hidden: bool,
+
+    #[arg(
+        long = "ignore-file",
+        value_delimiter = ',',
+        help = "Additional ignore files using gitignore syntax"
+    )]
+    pub ignore_files: Option<Vec<PathBuf>>,
+
+    #[arg(
+        long = "include",
+        value_delimiter = ',',
+        help = "Only include files matching these globs, relative to --path (e.g. 'src/**/*.rs')"
+    )]
+    pub includes: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        help = "Read newline-separated file paths from this file ('-' for stdin) instead of walking --path"
+    )]
+    pub files_from: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "50",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "Minimum line similarity (percent) for a deleted and an added file to be shown as a rename"
+    )]
+    pub rename_threshold: u8,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [ChangeType::Add, ChangeType::Modify, ChangeType::Delete, ChangeType::Rename],
+        help = "Kinds of git changes to show"
+    )]
+    pub change_types: Vec<ChangeType>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "git_from", "git_to", "commit", "since"],
+        help = "Print each file's line and byte counts and a total instead of its contents"
+    )]
+    pub stats_only: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SortOrder::Path,
+        help = "Order in which walked files are printed"
+    )]
+    pub sort: SortOrder,
+
+    #[arg(
+        long = "exclude-dir",
+        value_delimiter = ',',
+        help = "Skip directories with these names anywhere in the walk (e.g. 'target,node_modules')"
+    )]
+    pub exclude_dirs: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "git_from", "git_to", "commit", "since", "stats_only"],
+        help = "List the files that would be included, and their total size, without printing them"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "git_from", "git_to", "commit", "since", "stats_only", "dry_run"],
+        help = "Start the output with a numbered list of every included file"
+    )]
+    pub toc: bool,
+
+    #[arg(
+        long,
+        help = "Print file paths as walked, including the --path prefix, instead of relative to it"
+    )]
+    pub absolute_paths: bool,
+
+    #[arg(long, help = "Show a running count of walked files on stderr")]
+    pub progress: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["git_from", "git_to", "commit", "since", "diff_path"],
+        help = "Annotate each file header with the author and date of its last commit"
+    )]
+    pub blame: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Print at most N lines of each file, noting how many were left out"
+    )]
+    pub head: Option<usize>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Extensions to treat as binary in addition to the built-in list, e.g. json,csv"
+    )]
+    pub binary_extensions: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Extensions to read as text even though the built-in list treats them as binary, e.g. svg"
+    )]
+    pub text_extensions: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Don't print per-file warnings (skipped or unreadable files) to stderr"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        short,
+        long,
+        action = clap::ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Print diagnostics to stderr; repeat (-vv) to also list why each file was skipped"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        long,
+        help = "Leave out blank and whitespace-only lines when printing whole files"
+    )]
+    pub ignore_blank_lines: bool,
+
+    #[arg(
+        long,
+        help = "Follow symbolic links to files and directories; symlink loops are reported and skipped"
+    )]
+    pub follow_symlinks: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Only include files at most N directories deep; 1 means files directly under --path"
+    )]
+    pub max_depth: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Leave the line numbers out of --pattern match blocks so lines can be copied verbatim"
+    )]
+    pub no_line_numbers: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "1000",
+        help = "Skip files whose lines average more than N bytes, such as minified code; 0 keeps them"
+    )]
+    pub max_line_length: usize,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "7",
+        help = "Number of hex digits shown for object ids in git diffs; 0 shows them in full"
+    )]
+    pub abbrev: usize,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["stats_only", "dry_run"],
+        help = "Wrap each Markdown file section in a collapsible <details> element, for GitHub comments"
+    )]
+    pub collapsible: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["git_from", "git_to", "commit", "since", "diff_path"],
+        help = "Also write a JSON manifest of every printed file's path, content hash, size and line count"
+    )]
+    pub manifest: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["git_from", "git_to", "commit", "since", "diff_path"],
+        help = "Only include files that are new or changed since a manifest written by --manifest"
+    )]
+    pub since_manifest: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Break printed file lines longer than N characters onto continuation lines"
+    )]
+    pub wrap: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Group the --stats-only directory breakdown by the first N directories of each path"
+    )]
+    pub group_depth: u32,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10,
+        help = "List the N files with the most lines after the --stats-only and --dry-run totals (0 disables)"
+    )]
+    pub top: usize,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Also write a JSON summary of per-file and per-directory line and byte counts, the active filters and the diffstat"
+    )]
+    pub summary_json: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeType {
+    Add,
+    Modify,
+    Delete,
+    Rename,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Alphabetically by path
+    #[default]
+    Path,
+    /// Largest files first
+    Size,
+}
+
+/// Parses a byte size with an optional binary `k`, `m` or `g` suffix.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let lower = lower.strip_suffix('b').unwrap_or(&lower);
+    let (digits, multiplier) = match lower.chars().last() {
+        Some('k') => (&lower[..lower.len() - 1], 1024),
+        Some('m') => (&lower[..lower.len() - 1], 1024 * 1024),
+        Some('g') => (&lower[..lower.len() - 1], 1024 * 1024 * 1024),
+        _ => (lower, 1),
+    };
+
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{}'", trimmed))
 }
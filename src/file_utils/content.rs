@@ -1,5 +1,6 @@
-use gix::Repository;
-use regex::Regex;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 
 pub fn file_extension_matches(path: impl AsRef<Path>, extensions: &[String]) -> bool {
@@ -12,55 +13,121 @@ pub fn file_extension_matches(path: impl AsRef<Path>, extensions: &[String]) ->
     extensions.iter().any(|ext| ext == extension)
 }
 
-pub fn is_likely_binary(path: &std::path::Path) -> bool {
-    let extension = path
-        .extension()
-        .and_then(|os_str| os_str.to_str())
-        .unwrap_or("");
+/// Extensions of files that are never worth reading as text: images, fonts,
+/// archives, office documents, media, databases and compiled artifacts.
+/// `svg` is XML, but it is usually machine-generated and very long.
+pub const BINARY_EXTENSIONS: &[&str] = &[
+    // images
+    "jpg", "jpeg", "png", "gif", "bmp", "tiff", "ico", "svg",
+    // fonts
+    "woff", "woff2", "ttf", "otf", "eot",
+    // documents
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx",
+    // archives
+    "zip", "tar", "gz", "7z", "rar", "jar", "war",
+    // executables, libraries and object code
+    "exe", "dll", "so", "dylib", "o", "a", "class", "wasm", "bin",
+    // media
+    "mp3", "mp4", "avi", "mov", "flv",
+    // databases
+    "db", "sqlite",
+];
 
-    match extension.to_lowercase().as_str() {
-        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tiff" | "pdf" | "doc" | "docx" | "xls"
-        | "xlsx" | "ppt" | "pptx" | "zip" | "tar" | "gz" | "7z" | "rar" | "exe" | "dll" | "so"
-        | "dylib" | "mp3" | "mp4" | "avi" | "mov" | "flv" | "db" | "sqlite" => true,
-        _ => false,
+/// The extensions treated as binary without looking at a file's content:
+/// [`BINARY_EXTENSIONS`] by default, adjusted by `--binary-extensions` and
+/// `--text-extensions`. Extensions are compared case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryExtensions {
+    extensions: HashSet<String>,
+}
+
+impl Default for BinaryExtensions {
+    fn default() -> Self {
+        BinaryExtensions {
+            extensions: BINARY_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+        }
     }
 }
 
-pub fn print_file_content(
-    repo: &Repository,
-    oid: gix::ObjectId,
-    prefix: &str,
-    pattern: &Option<Regex>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let object = repo.find_object(oid)?;
-    let content = object.data.as_slice();
+impl BinaryExtensions {
+    /// The default set with `binary` added and `text` removed. A leading dot
+    /// on an extension is ignored.
+    pub fn new(binary: &[String], text: &[String]) -> Self {
+        let mut set = BinaryExtensions::default();
+        for ext in binary {
+            set.extensions.insert(normalize_extension(ext));
+        }
+        for ext in text {
+            set.extensions.remove(&normalize_extension(ext));
+        }
+        set
+    }
 
-    let mut start = 0;
-    while start < content.len() {
-        let end = content[start..]
-            .iter()
-            .position(|&b| b == b'\n')
-            .map_or(content.len(), |i| start + i);
-        let line = &content[start..end];
+    pub fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|os_str| os_str.to_str())
+            .is_some_and(|ext| self.extensions.contains(&ext.to_lowercase()))
+    }
+}
 
-        match std::str::from_utf8(line) {
-            Ok(utf8_line) => {
-                if let Some(ref regex) = pattern {
-                    if regex.is_match(utf8_line) {
-                        println!("{}{}", prefix, utf8_line);
-                    }
-                } else {
-                    println!("{}{}", prefix, utf8_line);
-                }
-            }
-            Err(_) => {
-                // You can choose to log this if needed
-                eprintln!("Skipping non-UTF-8 data in file: {}", oid);
-            }
+fn normalize_extension(ext: &str) -> String {
+    ext.trim_start_matches('.').to_lowercase()
+}
+
+/// How many leading bytes are inspected when sniffing a file's content.
+const SNIFF_LEN: usize = 8 * 1024;
+
+pub fn is_likely_binary(path: &std::path::Path, extensions: &BinaryExtensions) -> bool {
+    extensions.matches(path) || has_binary_content(path)
+}
+
+/// Same heuristic as [`is_likely_binary`], applied to a blob that is already
+/// in memory, such as a git object.
+pub fn is_binary_blob(path: &Path, data: &[u8], extensions: &BinaryExtensions) -> bool {
+    extensions.matches(path) || is_binary_bytes(data)
+}
+
+fn has_binary_content(path: &Path) -> bool {
+    let mut buf = [0u8; SNIFF_LEN];
+    let read = match File::open(path).and_then(|mut file| read_up_to(&mut file, &mut buf)) {
+        Ok(read) => read,
+        Err(_) => return false,
+    };
+    is_binary_bytes(&buf[..read])
+}
+
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match reader.read(&mut buf[total..])? {
+            0 => break,
+            n => total += n,
         }
+    }
+    Ok(total)
+}
 
-        start = end + 1;
+/// Treats content as binary if it contains a NUL byte or if more than 30% of
+/// it is control characters that do not appear in text files. Only the first
+/// 8 KiB are inspected.
+pub fn is_binary_bytes(bytes: &[u8]) -> bool {
+    let bytes = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if bytes.is_empty() {
+        return false;
+    }
+    // UTF-16 text is full of NUL bytes but is still text.
+    if encoding_rs::Encoding::for_bom(bytes).is_some() {
+        return false;
+    }
+    if bytes.contains(&0) {
+        return true;
     }
 
-    Ok(())
+    let suspicious = bytes
+        .iter()
+        .filter(|&&b| {
+            (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || b == 0x7f
+        })
+        .count();
+    suspicious * 10 > bytes.len() * 3
 }
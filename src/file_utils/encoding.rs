@@ -0,0 +1,36 @@
+use encoding_rs::{Encoding, WINDOWS_1252};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Reads a text file, decoding it from UTF-8, UTF-16 (with a BOM) or, as a
+/// last resort, Latin-1/Windows-1252.
+///
+/// Returns `Ok(None)` when the content looks binary rather than like text in
+/// some encoding.
+pub fn read_text_file(path: impl AsRef<Path>) -> io::Result<Option<String>> {
+    let bytes = fs::read(path)?;
+    Ok(decode_text(&bytes))
+}
+
+pub fn decode_text(bytes: &[u8]) -> Option<String> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Some(text.into_owned());
+    }
+
+    if bytes.contains(&0) {
+        return None;
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Some(text.to_string());
+    }
+
+    let (text, _, had_errors) = WINDOWS_1252.decode(bytes);
+    if had_errors {
+        None
+    } else {
+        Some(text.into_owned())
+    }
+}
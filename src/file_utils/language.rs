@@ -0,0 +1,87 @@
+use clap::ValueEnum;
+
+/// Languages accepted by `--languages`, each standing for the file extensions
+/// it is usually written in.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Go,
+    Python,
+    Javascript,
+    Typescript,
+    Java,
+    C,
+    Cpp,
+    Ruby,
+    Shell,
+    Markdown,
+    Toml,
+    Yaml,
+    Json,
+    Html,
+    Css,
+    Sql,
+}
+
+impl Language {
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["rs"],
+            Language::Go => &["go"],
+            Language::Python => &["py", "pyi"],
+            Language::Javascript => &["js", "mjs", "cjs", "jsx"],
+            Language::Typescript => &["ts", "mts", "cts", "tsx"],
+            Language::Java => &["java"],
+            Language::C => &["c", "h"],
+            Language::Cpp => &["cpp", "cc", "cxx", "hpp", "hh", "hxx", "h"],
+            Language::Ruby => &["rb"],
+            Language::Shell => &["sh", "bash", "zsh"],
+            Language::Markdown => &["md", "markdown"],
+            Language::Toml => &["toml"],
+            Language::Yaml => &["yaml", "yml"],
+            Language::Json => &["json"],
+            Language::Html => &["html", "htm"],
+            Language::Css => &["css"],
+            Language::Sql => &["sql"],
+        }
+    }
+
+    /// The language a file extension most likely belongs to. `h` headers are
+    /// counted as C.
+    pub fn from_extension(extension: &str) -> Option<Language> {
+        let extension = extension.to_lowercase();
+        Language::value_variants()
+            .iter()
+            .copied()
+            .find(|language| language.extensions().contains(&extension.as_str()))
+    }
+
+    /// The info string that tags a Markdown code fence in this language.
+    pub fn fence_tag(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Go => "go",
+            Language::Python => "python",
+            Language::Javascript => "javascript",
+            Language::Typescript => "typescript",
+            Language::Java => "java",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::Ruby => "ruby",
+            Language::Shell => "bash",
+            Language::Markdown => "markdown",
+            Language::Toml => "toml",
+            Language::Yaml => "yaml",
+            Language::Json => "json",
+            Language::Html => "html",
+            Language::Css => "css",
+            Language::Sql => "sql",
+        }
+    }
+
+    /// The name `--languages` accepts for this language, e.g. `rust`.
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string())
+    }
+}
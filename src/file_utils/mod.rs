@@ -1 +1,3 @@
 pub mod content;
+pub mod encoding;
+pub mod language;
@@ -1,11 +1,20 @@
+use gix::bstr::{BString, ByteSlice};
 use gix::diff::tree::{Changes, Recorder, State};
+use gix::objs::tree::EntryMode;
 use gix::objs::TreeRefIter;
-use gix::Repository;
+use gix::{ObjectId, Repository};
+use std::collections::HashMap;
 
 use gix::diff::tree::recorder::Change;
 
-pub fn diff_trees<'a>(
-    repo: &'a Repository,
+use crate::git::worktree::{blob_data, WorktreeBlobs};
+
+/// Past this many deletions times this many additions, [`detect_renames`]
+/// only pairs identical blobs, as git does with `diff.renameLimit`.
+pub const RENAME_LIMIT: usize = 1000;
+
+pub fn diff_trees(
+    repo: &Repository,
     previous_tree: TreeRefIter,
     current_tree: TreeRefIter,
 ) -> Result<Vec<Change>, Box<dyn std::error::Error>> {
@@ -20,3 +29,279 @@ pub fn diff_trees<'a>(
     )?;
     Ok(recorder.records)
 }
+
+/// A file-level change between two trees, independent of gix's recorder
+/// types. Directory entries are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileChange {
+    Added {
+        path: String,
+        oid: ObjectId,
+    },
+    Deleted {
+        path: String,
+        oid: ObjectId,
+    },
+    Modified {
+        path: String,
+        previous_oid: ObjectId,
+        oid: ObjectId,
+    },
+    Renamed {
+        from: String,
+        to: String,
+        previous_oid: ObjectId,
+        oid: ObjectId,
+        similarity: f32,
+    },
+}
+
+impl From<Change> for FileChange {
+    fn from(change: Change) -> Self {
+        match change {
+            Change::Addition { oid, path, .. } => FileChange::Added {
+                path: path.to_str_lossy().into_owned(),
+                oid,
+            },
+            Change::Deletion { oid, path, .. } => FileChange::Deleted {
+                path: path.to_str_lossy().into_owned(),
+                oid,
+            },
+            Change::Modification {
+                previous_oid,
+                oid,
+                path,
+                ..
+            } => FileChange::Modified {
+                path: path.to_str_lossy().into_owned(),
+                previous_oid,
+                oid,
+            },
+        }
+    }
+}
+
+impl From<Rename> for FileChange {
+    fn from(rename: Rename) -> Self {
+        FileChange::Renamed {
+            from: rename.from.to_str_lossy().into_owned(),
+            to: rename.to.to_str_lossy().into_owned(),
+            previous_oid: rename.previous_oid,
+            oid: rename.oid,
+            similarity: rename.similarity,
+        }
+    }
+}
+
+/// Diffs two trees and returns the file changes, with renames detected at
+/// `rename_threshold` (0.0 to 1.0) as in [`detect_renames`].
+pub fn file_changes(
+    repo: &Repository,
+    previous_tree: TreeRefIter,
+    current_tree: TreeRefIter,
+    rename_threshold: f32,
+) -> Result<Vec<FileChange>, Box<dyn std::error::Error>> {
+    let changes: Vec<Change> = diff_trees(repo, previous_tree, current_tree)?
+        .into_iter()
+        .filter(|change| match change {
+            Change::Addition { entry_mode, .. }
+            | Change::Deletion { entry_mode, .. }
+            | Change::Modification { entry_mode, .. } => !entry_mode.is_tree(),
+        })
+        .collect();
+    let (changes, renames) =
+        detect_renames(repo, &WorktreeBlobs::new(), changes, rename_threshold)?;
+
+    Ok(renames
+        .into_iter()
+        .map(FileChange::from)
+        .chain(changes.into_iter().map(FileChange::from))
+        .collect())
+}
+
+/// A deleted path paired with an added path whose content is identical or
+/// similar enough to treat the two as one renamed file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub from: BString,
+    pub to: BString,
+    pub previous_entry_mode: EntryMode,
+    pub previous_oid: ObjectId,
+    pub entry_mode: EntryMode,
+    pub oid: ObjectId,
+    /// Fraction of lines shared by both sides, from 0.0 to 1.0.
+    pub similarity: f32,
+}
+
+/// Pairs up deletions and additions in `changes` that look like renames.
+///
+/// Blobs with the same id are always paired; otherwise a deletion is paired
+/// with the most similar remaining addition if their line similarity is at
+/// least `threshold` (0.0 to 1.0), unless there are too many pairs to compare
+/// (see [`RENAME_LIMIT`]). Blob contents are looked up in `worktree`
+/// before the object database. Returns the changes that were not paired, in
+/// their original order, and the detected renames.
+pub fn detect_renames(
+    repo: &Repository,
+    worktree: &WorktreeBlobs,
+    changes: Vec<Change>,
+    threshold: f32,
+) -> Result<(Vec<Change>, Vec<Rename>), Box<dyn std::error::Error>> {
+    let is_candidate = |change: &Change| match change {
+        Change::Addition { entry_mode, .. } | Change::Deletion { entry_mode, .. } => {
+            entry_mode.is_blob()
+        }
+        Change::Modification { .. } => false,
+    };
+    let deletions: Vec<usize> = (0..changes.len())
+        .filter(|&i| is_candidate(&changes[i]) && matches!(changes[i], Change::Deletion { .. }))
+        .collect();
+    let additions: Vec<usize> = (0..changes.len())
+        .filter(|&i| is_candidate(&changes[i]) && matches!(changes[i], Change::Addition { .. }))
+        .collect();
+
+    let mut pairs: Vec<(usize, usize, f32)> = Vec::new();
+    let mut paired = vec![false; changes.len()];
+
+    for &d in &deletions {
+        let deleted_oid = change_oid(&changes[d]);
+        if let Some(&a) = additions
+            .iter()
+            .find(|&&a| !paired[a] && change_oid(&changes[a]) == deleted_oid)
+        {
+            paired[d] = true;
+            paired[a] = true;
+            pairs.push((d, a, 1.0));
+        }
+    }
+
+    let unpaired = |indices: &[usize]| -> Vec<usize> {
+        indices.iter().copied().filter(|&i| !paired[i]).collect()
+    };
+    let (deletions, additions) = (unpaired(&deletions), unpaired(&additions));
+    if deletions.len() * additions.len() > RENAME_LIMIT * RENAME_LIMIT {
+        return Ok(finish_renames(changes, paired, pairs));
+    }
+
+    let mut added_data = Vec::with_capacity(additions.len());
+    for &a in &additions {
+        added_data.push(blob_data(repo, worktree, change_oid(&changes[a]))?.into_owned());
+    }
+    let added_lines: Vec<LineHistogram> = added_data
+        .iter()
+        .map(|data| LineHistogram::new(data))
+        .collect();
+
+    for &d in &deletions {
+        let deleted_data = blob_data(repo, worktree, change_oid(&changes[d]))?;
+        let deleted = LineHistogram::new(&deleted_data);
+
+        let mut best: Option<(usize, f32)> = None;
+        for (i, &a) in additions.iter().enumerate() {
+            if paired[a] {
+                continue;
+            }
+            let score = deleted.similarity(&added_lines[i]);
+            if score >= threshold && best.is_none_or(|(_, s)| score > s) {
+                best = Some((a, score));
+            }
+        }
+
+        if let Some((a, score)) = best {
+            paired[d] = true;
+            paired[a] = true;
+            pairs.push((d, a, score));
+        }
+    }
+
+    Ok(finish_renames(changes, paired, pairs))
+}
+
+/// Turns the paired `(deletion, addition, similarity)` indices into
+/// [`Rename`]s and returns them with the changes left unpaired.
+fn finish_renames(
+    changes: Vec<Change>,
+    paired: Vec<bool>,
+    pairs: Vec<(usize, usize, f32)>,
+) -> (Vec<Change>, Vec<Rename>) {
+    let renames = pairs
+        .into_iter()
+        .map(|(d, a, similarity)| match (&changes[d], &changes[a]) {
+            (
+                Change::Deletion {
+                    entry_mode: previous_entry_mode,
+                    oid: previous_oid,
+                    path: from,
+                },
+                Change::Addition {
+                    entry_mode,
+                    oid,
+                    path: to,
+                },
+            ) => Rename {
+                from: from.clone(),
+                to: to.clone(),
+                previous_entry_mode: *previous_entry_mode,
+                previous_oid: *previous_oid,
+                entry_mode: *entry_mode,
+                oid: *oid,
+                similarity,
+            },
+            _ => unreachable!("renames pair a deletion with an addition"),
+        })
+        .collect();
+
+    let remaining = changes
+        .into_iter()
+        .zip(paired)
+        .filter_map(|(change, paired)| (!paired).then_some(change))
+        .collect();
+
+    (remaining, renames)
+}
+
+fn change_oid(change: &Change) -> ObjectId {
+    match change {
+        Change::Addition { oid, .. }
+        | Change::Deletion { oid, .. }
+        | Change::Modification { oid, .. } => *oid,
+    }
+}
+
+/// The lines of a blob with how often each occurs, built once per blob so
+/// rename detection does not recount them for every pair.
+struct LineHistogram<'a> {
+    counts: HashMap<&'a [u8], usize>,
+    total: usize,
+}
+
+impl<'a> LineHistogram<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut counts: HashMap<&[u8], usize> = HashMap::new();
+        let mut total = 0;
+        for line in data.lines() {
+            *counts.entry(line).or_default() += 1;
+            total += 1;
+        }
+        LineHistogram { counts, total }
+    }
+
+    /// Dice coefficient over the two multisets of lines.
+    fn similarity(&self, other: &LineHistogram) -> f32 {
+        if self.total == 0 && other.total == 0 {
+            return 1.0;
+        }
+        let (small, large) = if self.counts.len() <= other.counts.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let shared: usize = small
+            .counts
+            .iter()
+            .map(|(line, &count)| count.min(large.counts.get(line).copied().unwrap_or(0)))
+            .sum();
+
+        (2 * shared) as f32 / (self.total + other.total) as f32
+    }
+}
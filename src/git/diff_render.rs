@@ -0,0 +1,84 @@
+use crate::output::formatter::plural;
+use regex::Regex;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::fmt;
+
+/// Renders a unified diff between two versions of a file, keeping `context`
+/// unchanged lines around each change.
+///
+/// When `pattern` is given, only hunks with an added or removed line matching
+/// it are kept. Returns an empty string if nothing is left to show.
+pub fn unified_diff(
+    old: &str,
+    new: &str,
+    old_label: &str,
+    new_label: &str,
+    context: usize,
+    pattern: Option<&Regex>,
+) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let mut out = String::new();
+
+    for hunk in diff.unified_diff().context_radius(context).iter_hunks() {
+        if let Some(regex) = pattern {
+            let matches = hunk
+                .iter_changes()
+                .any(|change| change.tag() != ChangeTag::Equal && regex.is_match(change.value()));
+            if !matches {
+                continue;
+            }
+        }
+
+        if out.is_empty() {
+            out.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+        }
+        out.push_str(&hunk.to_string());
+    }
+
+    out
+}
+
+/// Counts the lines inserted into and deleted from `old` to produce `new`.
+pub fn count_line_changes(old: &str, new: &str) -> (usize, usize) {
+    let diff = TextDiff::from_lines(old, new);
+    diff.iter_all_changes()
+        .fold((0, 0), |(insertions, deletions), change| {
+            match change.tag() {
+                ChangeTag::Insert => (insertions + 1, deletions),
+                ChangeTag::Delete => (insertions, deletions + 1),
+                ChangeTag::Equal => (insertions, deletions),
+            }
+        })
+}
+
+/// Totals for a `git diff --shortstat` style summary line.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiffStat {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl DiffStat {
+    pub fn record(&mut self, insertions: usize, deletions: usize) {
+        self.files_changed += 1;
+        self.insertions += insertions;
+        self.deletions += deletions;
+    }
+}
+
+impl fmt::Display for DiffStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} changed, {} {}(+), {} {}(-)",
+            self.files_changed,
+            plural(self.files_changed, "file", "files"),
+            self.insertions,
+            plural(self.insertions, "insertion", "insertions"),
+            self.deletions,
+            plural(self.deletions, "deletion", "deletions"),
+        )
+    }
+}
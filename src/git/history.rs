@@ -0,0 +1,106 @@
+use crate::git::diff::diff_trees;
+use crate::git::repository::empty_tree;
+use gix::bstr::BString;
+use gix::diff::tree::recorder::Change;
+use gix::objs::TreeRefIter;
+use gix::{ObjectId, Repository};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The most recent commit that changed a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastChange {
+    pub author: String,
+    /// Commit date as `YYYY-MM-DD`.
+    pub date: String,
+    pub id: ObjectId,
+}
+
+impl fmt::Display for LastChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "last changed by {} on {} ({})",
+            self.author,
+            self.date,
+            self.id.to_hex_with_len(7)
+        )
+    }
+}
+
+/// Finds the newest commit reachable from HEAD whose version of `path` (a
+/// repository-relative path) differs from its first parent's.
+///
+/// Returns `None` for paths that no commit touched, e.g. untracked files, or
+/// when HEAD is unborn. Use [`last_changes`] for more than one path.
+pub fn last_change(
+    repo: &Repository,
+    path: &Path,
+) -> Result<Option<LastChange>, Box<dyn std::error::Error>> {
+    Ok(last_changes(repo, &[path.to_path_buf()])?.remove(path))
+}
+
+/// Like [`last_change`] for many paths at once: history is walked a single
+/// time, diffing each commit against its first parent, and stops as soon as
+/// every path has been found. Paths no commit touched are left out of the
+/// returned map.
+pub fn last_changes(
+    repo: &Repository,
+    paths: &[PathBuf],
+) -> Result<HashMap<PathBuf, LastChange>, Box<dyn std::error::Error>> {
+    let mut wanted: HashMap<BString, &PathBuf> = paths
+        .iter()
+        .map(|path| {
+            let key =
+                gix::path::to_unix_separators_on_windows(gix::path::into_bstr(path.as_path()));
+            (key.into_owned(), path)
+        })
+        .collect();
+    let mut found = HashMap::new();
+    let Ok(head) = repo.head_id() else {
+        return Ok(found);
+    };
+
+    for info in head.ancestors().all()? {
+        if wanted.is_empty() {
+            break;
+        }
+        let commit = info?.object()?;
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent_ids().next() {
+            Some(parent) => Some(parent.object()?.into_commit().tree()?),
+            None => None,
+        };
+        let previous = match parent_tree {
+            Some(ref tree) => TreeRefIter::from_bytes(&tree.data),
+            None => empty_tree(),
+        };
+
+        for change in diff_trees(repo, previous, TreeRefIter::from_bytes(&tree.data))? {
+            let (Change::Addition {
+                entry_mode, path, ..
+            }
+            | Change::Modification {
+                entry_mode, path, ..
+            }) = change
+            else {
+                continue;
+            };
+            if !entry_mode.is_blob_or_symlink() {
+                continue;
+            }
+            if let Some(requested) = wanted.remove(&path) {
+                found.insert(
+                    requested.clone(),
+                    LastChange {
+                        author: commit.author()?.name.to_string(),
+                        date: commit.time()?.format(gix::date::time::format::SHORT),
+                        id: commit.id,
+                    },
+                );
+            }
+        }
+    }
+    Ok(found)
+}
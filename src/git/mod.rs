@@ -1,2 +1,5 @@
 pub mod diff;
+pub mod diff_render;
+pub mod history;
 pub mod repository;
+pub mod worktree;
@@ -1,11 +1,24 @@
 use gix::objs::Find;
-use gix::{objs::TreeRefIter, Repository};
+use gix::{objs::TreeRefIter, ObjectId, Repository};
 use std::path::Path;
 
+/// Opens the repository containing `dir`, searching parent directories the
+/// way `git` does, so any path inside a working tree works.
+///
+/// Relative paths are made absolute first, since discovery only walks up the
+/// components of the path it is given.
 pub fn open_repo(dir: impl AsRef<Path>) -> Result<Repository, Box<dyn std::error::Error>> {
-    let git = gix::open::Options::isolated()
-        .filter_config_section(|_| false)
-        .open(dir.as_ref())?;
+    let dir = dir.as_ref();
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let options = gix::open::Options::isolated().filter_config_section(|_| false);
+    let git = gix::ThreadSafeRepository::discover_opts(
+        &dir,
+        Default::default(),
+        gix::sec::trust::Mapping {
+            full: options.clone(),
+            reduced: options,
+        },
+    )?;
 
     Ok(git.to_thread_local())
 }
@@ -26,6 +39,34 @@ pub fn find_revision<'a>(
     }
 }
 
+/// Like [`find_revision`], except that the parent of a root commit
+/// (`<root>^` or `<root>~1`) resolves to `None`, which callers treat as the
+/// empty tree.
+pub fn find_revision_or_empty<'a>(
+    repo: &'a Repository,
+    revision_name: &str,
+) -> Result<Option<gix::Object<'a>>, Box<dyn std::error::Error>> {
+    let err = match find_revision(repo, revision_name) {
+        Ok(obj) => return Ok(Some(obj)),
+        Err(e) => e,
+    };
+
+    let base = ["^1", "^", "~1", "~"]
+        .iter()
+        .find_map(|suffix| revision_name.strip_suffix(suffix));
+    if let Some(base) = base {
+        let commit = find_revision(repo, base)
+            .ok()
+            .and_then(|obj| obj.peel_to_kind(gix::object::Kind::Commit).ok());
+        if let Some(commit) = commit {
+            if commit.into_commit().parent_ids().next().is_none() {
+                return Ok(None);
+            }
+        }
+    }
+    Err(err)
+}
+
 pub fn find_tree<'a>(
     repo: &'a Repository,
     obj: gix::Object<'a>,
@@ -34,7 +75,44 @@ pub fn find_tree<'a>(
     let db = &repo.objects;
     let tree = obj.peel_to_tree()?;
     let tree_id = tree.id();
-    let data = db.try_find(&tree_id, buf).unwrap().unwrap();
-    let tree = data.try_into_tree_iter().unwrap();
+    let data = db
+        .try_find(&tree_id, buf)
+        .map_err(|e| format!("failed to read tree object {}: {}", tree_id, e))?
+        .ok_or_else(|| format!("tree object {} not found in odb", tree_id))?;
+    let tree = data
+        .try_into_tree_iter()
+        .ok_or_else(|| format!("object {} is not a tree", tree_id))?;
     Ok(tree)
 }
+
+/// An iterator over the empty tree, used as the "before" side when a commit
+/// has no parent.
+pub fn empty_tree() -> TreeRefIter<'static> {
+    TreeRefIter::from_bytes(&[])
+}
+
+/// Looks up the blob at `path` (relative to the repository root, nested
+/// paths allowed) in the tree of `rev`.
+///
+/// Returns `None` if nothing exists at `path` in that revision or if it names
+/// a directory or submodule rather than a file.
+pub fn resolve_blob_at(
+    repo: &Repository,
+    path: impl AsRef<Path>,
+    rev: &str,
+) -> Result<Option<ObjectId>, Box<dyn std::error::Error>> {
+    let tree = find_revision(repo, rev)?.peel_to_tree()?;
+    blob_in_tree(&tree, path.as_ref())
+}
+
+/// The id of the blob at `path` in `tree`, if there is one.
+pub(crate) fn blob_in_tree(
+    tree: &gix::Tree<'_>,
+    path: &Path,
+) -> Result<Option<ObjectId>, Box<dyn std::error::Error>> {
+    let mut buf = Vec::new();
+    let entry = tree.lookup_entry_by_path(path, &mut buf)?;
+    Ok(entry
+        .filter(|entry| entry.mode().is_blob_or_symlink())
+        .map(|entry| entry.object_id()))
+}
@@ -0,0 +1,193 @@
+use gix::bstr::{BString, ByteSlice};
+use gix::diff::tree::recorder::Change;
+use gix::objs::tree::EntryMode;
+use gix::traverse::tree::Recorder;
+use gix::{ObjectId, Repository};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Revision name that stands for the current working directory.
+pub const WORKTREE: &str = "WORKTREE";
+
+/// Contents of working-directory files that are not in the object database,
+/// keyed by the blob id they would be stored under.
+pub type WorktreeBlobs = HashMap<ObjectId, Vec<u8>>;
+
+/// Returns the contents of blob `oid`, taken from `worktree` when it holds
+/// them and from the object database otherwise.
+pub fn blob_data<'a>(
+    repo: &Repository,
+    worktree: &'a WorktreeBlobs,
+    oid: ObjectId,
+) -> Result<Cow<'a, [u8]>, Box<dyn std::error::Error>> {
+    match worktree.get(&oid) {
+        Some(data) => Ok(Cow::Borrowed(data)),
+        None => Ok(Cow::Owned(repo.find_object(oid)?.detach().data)),
+    }
+}
+
+#[derive(Default)]
+struct TrackedPath {
+    in_tree: Option<(EntryMode, ObjectId)>,
+    index_mode: Option<EntryMode>,
+}
+
+/// Compares the tree `tree_id` with the files on disk.
+///
+/// Every path tracked by the tree or the index is read from the working
+/// directory, so both staged and unstaged edits show up; untracked files and
+/// submodules are not included. Contents that differ from the tree are kept in `blobs` under
+/// the id `git add` would give them, so the returned changes can be printed
+/// like any other diff without writing to the object database.
+pub fn diff_tree_to_worktree(
+    repo: &Repository,
+    tree_id: ObjectId,
+    blobs: &mut WorktreeBlobs,
+) -> Result<Vec<Change>, Box<dyn std::error::Error>> {
+    let work_dir = repo
+        .work_dir()
+        .ok_or("WORKTREE cannot be used with a bare repository")?
+        .to_owned();
+
+    let mut recorder = Recorder::default();
+    repo.find_object(tree_id)?
+        .peel_to_tree()?
+        .traverse()
+        .breadthfirst(&mut recorder)?;
+
+    let mut paths: BTreeMap<BString, TrackedPath> = BTreeMap::new();
+    for entry in recorder
+        .records
+        .into_iter()
+        .filter(|e| e.mode.is_blob_or_symlink())
+    {
+        paths.entry(entry.filepath).or_default().in_tree = Some((entry.mode, entry.oid));
+    }
+    let index = repo.index_or_empty()?;
+    for entry in index.entries() {
+        if let Some(mode) = entry
+            .mode
+            .to_tree_entry_mode()
+            .filter(|mode| mode.is_blob_or_symlink())
+        {
+            paths
+                .entry(entry.path(&index).to_owned())
+                .or_default()
+                .index_mode = Some(mode);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (
+        path,
+        TrackedPath {
+            in_tree,
+            index_mode,
+        },
+    ) in paths
+    {
+        let disk_path = work_dir.join(path.to_path_lossy());
+        let data = match fs::symlink_metadata(&disk_path) {
+            Ok(meta) if meta.file_type().is_symlink() => Some(
+                gix::path::into_bstr(fs::read_link(&disk_path)?)
+                    .into_owned()
+                    .into(),
+            ),
+            Ok(meta) if meta.is_file() => Some(fs::read(&disk_path)?),
+            _ => None,
+        };
+
+        match (in_tree, data) {
+            (Some((entry_mode, oid)), None) => changes.push(Change::Deletion {
+                entry_mode,
+                oid,
+                path,
+            }),
+            (Some((previous_entry_mode, previous_oid)), Some(data)) => {
+                let oid = gix::objs::compute_hash(repo.object_hash(), gix::objs::Kind::Blob, &data);
+                if oid != previous_oid {
+                    blobs.insert(oid, data);
+                    changes.push(Change::Modification {
+                        previous_entry_mode,
+                        previous_oid,
+                        entry_mode: index_mode.unwrap_or(previous_entry_mode),
+                        oid,
+                        path,
+                    });
+                }
+            }
+            (None, Some(data)) => {
+                let oid = gix::objs::compute_hash(repo.object_hash(), gix::objs::Kind::Blob, &data);
+                blobs.insert(oid, data);
+                changes.push(Change::Addition {
+                    entry_mode: index_mode
+                        .unwrap_or(EntryMode::from(gix::objs::tree::EntryKind::Blob)),
+                    oid,
+                    path,
+                });
+            }
+            (None, None) => {}
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Lists the files in the working directory that the index does not track,
+/// as additions. Ignored files are left out, following `.gitignore` and
+/// `.git/info/exclude`, as are submodules and nested repositories, and so is
+/// every file for which `keep`, given its repo-relative path and size,
+/// returns false. The others are read into `blobs`, as in
+/// [`diff_tree_to_worktree`].
+pub fn untracked_additions(
+    repo: &Repository,
+    blobs: &mut WorktreeBlobs,
+    mut keep: impl FnMut(&Path, u64) -> bool,
+) -> Result<Vec<Change>, Box<dyn std::error::Error>> {
+    let work_dir = repo
+        .work_dir()
+        .ok_or("untracked files cannot be listed in a bare repository")?
+        .to_owned();
+    let index = repo.index_or_empty()?;
+
+    let mut changes = Vec::new();
+    let walker = ignore::WalkBuilder::new(&work_dir)
+        .hidden(false)
+        .filter_entry(|entry| {
+            // Submodules and nested repositories are not untracked files of
+            // this one, so git does not list their contents either.
+            let nested_repo = entry.depth() > 0
+                && entry.file_type().is_some_and(|ft| ft.is_dir())
+                && entry.path().join(".git").exists();
+            entry.file_name() != ".git" && !nested_repo
+        })
+        .build();
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(&work_dir)?;
+        let path: BString =
+            gix::path::to_unix_separators_on_windows(gix::path::into_bstr(relative)).into_owned();
+        if index.entry_by_path(path.as_bstr()).is_some() {
+            continue;
+        }
+        if !keep(relative, entry.metadata()?.len()) {
+            continue;
+        }
+
+        let data = fs::read(entry.path())?;
+        let oid = gix::objs::compute_hash(repo.object_hash(), gix::objs::Kind::Blob, &data);
+        blobs.insert(oid, data);
+        changes.push(Change::Addition {
+            entry_mode: EntryMode::from(gix::objs::tree::EntryKind::Blob),
+            oid,
+            path,
+        });
+    }
+
+    Ok(changes)
+}
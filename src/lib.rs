@@ -1,9 +1,19 @@
 pub mod args;
 pub mod git;
 pub mod file_utils;
+pub mod output;
+pub mod snapshot;
 
 // Re-export commonly used items
 pub use args::Args;
-pub use git::repository::{open_repo, find_revision, find_tree};
-pub use git::diff::diff_trees;
-pub use file_utils::content::{is_likely_binary, file_extension_matches, print_file_content};
\ No newline at end of file
+pub use git::repository::{empty_tree, open_repo, find_revision, find_revision_or_empty, find_tree, resolve_blob_at};
+pub use git::diff::{detect_renames, diff_trees, file_changes, FileChange, Rename};
+pub use git::diff_render::{count_line_changes, unified_diff, DiffStat};
+pub use git::history::{last_change, last_changes, LastChange};
+pub use git::worktree::{blob_data, diff_tree_to_worktree, untracked_additions, WorktreeBlobs, WORKTREE};
+pub use file_utils::content::{is_binary_blob, is_binary_bytes, BinaryExtensions, is_likely_binary, file_extension_matches};
+pub use file_utils::encoding::read_text_file;
+pub use output::formatter::{Filters, OutputFormat, OutputFormatter};
+pub use output::manifest::{Manifest, ManifestEntry};
+pub use output::summary::{DirectoryTotals, Summary, SummaryFile};
+pub use snapshot::{formatter_for, run, FileReport, RunReport, SkipCounts};
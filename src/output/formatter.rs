@@ -0,0 +1,715 @@
+use crate::file_utils::language::Language;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Markdown-like text with one fenced block per file
+    #[default]
+    Plain,
+    /// A single JSON document, emitted once the walk is complete
+    Json,
+    /// A `##` heading per file and a fenced block tagged with its language
+    Markdown,
+    /// `<document>` elements under a `<documents>` root, as recommended for
+    /// Claude prompts
+    Xml,
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Filters {
+    pub extensions: Option<Vec<String>>,
+    pub excludes: Option<Vec<String>>,
+    pub includes: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+struct FileEntry {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    annotation: Option<String>,
+    contents: String,
+    /// Lines cut by `--head`, noted after the contents but not counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    omitted_lines: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+struct JsonSnapshot<'a> {
+    repository: String,
+    roots: &'a [String],
+    filters: &'a Filters,
+    total_files: usize,
+    files: &'a [FileEntry],
+}
+
+/// Renders walked files in the chosen [`OutputFormat`] to a sink, stdout by
+/// default. Any `Write` works, so tests can render into a `Vec<u8>`.
+pub struct OutputFormatter<W: Write = Box<dyn Write>> {
+    format: OutputFormat,
+    roots: Vec<String>,
+    filters: Filters,
+    files: Vec<FileEntry>,
+    sink: W,
+    stats: Option<SnapshotStats>,
+    toc: bool,
+    collapsible: bool,
+    wrap: Option<usize>,
+    match_tally: Option<MatchTally>,
+}
+
+/// Totals reported at the end of pattern mode.
+#[derive(Debug, Default)]
+struct MatchTally {
+    matches: usize,
+    files: usize,
+}
+
+/// Running totals for `--stats-only` and `--dry-run`.
+#[derive(Debug, Default)]
+struct SnapshotStats {
+    /// Lists bare paths instead of per-file counts (`--dry-run`).
+    paths_only: bool,
+    files: usize,
+    lines: usize,
+    bytes: usize,
+    /// File and line counts keyed by language, or by extension for files no
+    /// [`Language`] covers.
+    by_language: BTreeMap<String, (usize, usize)>,
+    /// File and line counts keyed by the first `group_depth` directories of
+    /// each path, `.` for files at the top.
+    by_directory: BTreeMap<String, (usize, usize)>,
+    group_depth: usize,
+    /// Path, line and byte counts of every file, for the `Largest files`
+    /// list.
+    sizes: Vec<(String, usize, usize)>,
+    top: usize,
+}
+
+impl OutputFormatter {
+    pub fn new(format: OutputFormat, root: impl AsRef<Path>) -> Self {
+        OutputFormatter {
+            format,
+            roots: vec![root.as_ref().display().to_string()],
+            filters: Filters::default(),
+            files: Vec::new(),
+            sink: Box::new(io::stdout()),
+            stats: None,
+            toc: false,
+            collapsible: false,
+            wrap: None,
+            match_tally: None,
+        }
+    }
+}
+
+impl<W: Write> OutputFormatter<W> {
+    /// Replaces the default stdout sink, e.g. with a file for `--output`.
+    pub fn with_sink<S: Write>(self, sink: S) -> OutputFormatter<S> {
+        OutputFormatter {
+            format: self.format,
+            roots: self.roots,
+            filters: self.filters,
+            files: self.files,
+            sink,
+            stats: self.stats,
+            toc: self.toc,
+            collapsible: self.collapsible,
+            wrap: self.wrap,
+            match_tally: self.match_tally,
+        }
+    }
+
+    /// Returns the sink, e.g. to inspect what was rendered into a buffer.
+    pub fn into_sink(self) -> W {
+        self.sink
+    }
+
+    pub fn with_filters(mut self, filters: Filters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Prints only per-file line and byte counts, followed by a total from
+    /// [`finish`](Self::finish), instead of file contents.
+    pub fn with_stats_only(mut self) -> Self {
+        self.stats = Some(SnapshotStats {
+            group_depth: 1,
+            ..SnapshotStats::default()
+        });
+        self
+    }
+
+    /// Lists only the path of each file that passes the filters, followed by
+    /// the same total as [`with_stats_only`](Self::with_stats_only).
+    pub fn with_dry_run(mut self) -> Self {
+        self.stats = Some(SnapshotStats {
+            paths_only: true,
+            group_depth: 1,
+            ..SnapshotStats::default()
+        });
+        self
+    }
+
+    /// Buckets the `By directory` breakdown of
+    /// [`with_stats_only`](Self::with_stats_only) and
+    /// [`with_dry_run`](Self::with_dry_run) by the first `depth` directories
+    /// of each path instead of only the first.
+    pub fn with_group_depth(mut self, depth: usize) -> Self {
+        if let Some(ref mut stats) = self.stats {
+            stats.group_depth = depth;
+        }
+        self
+    }
+
+    /// Ends the totals of [`with_stats_only`](Self::with_stats_only) and
+    /// [`with_dry_run`](Self::with_dry_run) with the `count` files that have
+    /// the most lines, largest first. `0` leaves the list out.
+    pub fn with_top(mut self, count: usize) -> Self {
+        if let Some(ref mut stats) = self.stats {
+            stats.top = count;
+        }
+        self
+    }
+
+    /// Opens plain and Markdown output with a numbered list of every file.
+    /// Files are held back until [`finish`](Self::finish) so the list is
+    /// complete, and per-root headers are omitted.
+    pub fn with_toc(mut self) -> Self {
+        self.toc = true;
+        self
+    }
+
+    /// Wraps each Markdown file section in a `<details>` element whose
+    /// summary shows the path and size, so GitHub renders it collapsed.
+    pub fn with_collapsible(mut self) -> Self {
+        self.collapsible = true;
+        self
+    }
+
+    /// Breaks plain and Markdown file lines longer than `width` characters
+    /// onto continuation lines. Line and size counts still describe the
+    /// original text.
+    pub fn with_wrap(mut self, width: usize) -> Self {
+        self.wrap = Some(width);
+        self
+    }
+
+    pub fn print_file_contents(&mut self, path: &Path, contents: &str) -> io::Result<()> {
+        self.print_annotated_file_contents(path, contents, None)
+    }
+
+    /// Like [`print_file_contents`](Self::print_file_contents), with a short
+    /// note such as `--blame`'s last change shown next to the path.
+    pub fn print_annotated_file_contents(
+        &mut self,
+        path: &Path,
+        contents: &str,
+        annotation: Option<&str>,
+    ) -> io::Result<()> {
+        self.print_file_head(path, contents, annotation, 0)
+    }
+
+    /// Like [`print_annotated_file_contents`](Self::print_annotated_file_contents)
+    /// for a file cut short by `--head`: `contents` is the printed part, and a
+    /// note of the `omitted_lines` that follow is shown after it without
+    /// counting towards the line and size totals.
+    pub fn print_file_head(
+        &mut self,
+        path: &Path,
+        contents: &str,
+        annotation: Option<&str>,
+        omitted_lines: usize,
+    ) -> io::Result<()> {
+        let lines = contents.lines().count();
+        if let Some(ref mut stats) = self.stats {
+            stats.files += 1;
+            stats.lines += lines;
+            stats.bytes += contents.len();
+            for bucket in [
+                stats.by_language.entry(language_label(path)).or_default(),
+                stats
+                    .by_directory
+                    .entry(directory_label(path, stats.group_depth))
+                    .or_default(),
+            ] {
+                bucket.0 += 1;
+                bucket.1 += lines;
+            }
+            stats
+                .sizes
+                .push((path.display().to_string(), lines, contents.len()));
+            if stats.paths_only {
+                return writeln!(self.sink, "{}", path.display());
+            }
+            return writeln!(
+                self.sink,
+                "File: {} ({} {}, {})",
+                path.display(),
+                lines,
+                plural(lines, "line", "lines"),
+                format_size(contents.len())
+            );
+        }
+
+        match self.format {
+            OutputFormat::Plain | OutputFormat::Markdown if !self.toc => {
+                write_file_section(
+                    &mut self.sink,
+                    self.format,
+                    path,
+                    contents,
+                    annotation,
+                    omitted_lines,
+                    self.collapsible,
+                    self.wrap,
+                )?;
+            }
+            _ => self.files.push(FileEntry {
+                path: path.display().to_string(),
+                annotation: annotation.map(str::to_string),
+                contents: contents.to_string(),
+                omitted_lines: (omitted_lines > 0).then_some(omitted_lines),
+            }),
+        }
+        Ok(())
+    }
+
+    /// Prints the matches found in a file in pattern mode. `rendered` holds
+    /// the match blocks with their context and captured groups, and `matches`
+    /// is the number of matching lines, which is tallied for the summary.
+    pub fn print_match_block(
+        &mut self,
+        path: &Path,
+        rendered: &str,
+        matches: usize,
+    ) -> io::Result<()> {
+        let tally = self.match_tally.get_or_insert_with(MatchTally::default);
+        tally.matches += matches;
+        if matches > 0 {
+            tally.files += 1;
+        }
+
+        match self.format {
+            OutputFormat::Plain => {
+                writeln!(self.sink, "### File: {}", path.display())?;
+                write!(self.sink, "{}", rendered)?;
+            }
+            OutputFormat::Markdown => {
+                writeln!(self.sink, "## {}", path.display())?;
+                writeln!(self.sink)?;
+                write!(self.sink, "{}", rendered)?;
+            }
+            OutputFormat::Json | OutputFormat::Xml => self.files.push(FileEntry {
+                path: path.display().to_string(),
+                annotation: None,
+                contents: rendered.to_string(),
+                omitted_lines: None,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Prints a bare path, for listings such as `--files-with-matches`.
+    pub fn print_path(&mut self, path: &Path) -> io::Result<()> {
+        writeln!(self.sink, "{}", path.display())
+    }
+
+    /// Registers another walk root when several `--path`s are snapshotted
+    /// together.
+    pub fn add_root(&mut self, root: impl AsRef<Path>) {
+        self.roots.push(root.as_ref().display().to_string());
+    }
+
+    pub fn print_root_header(&mut self, root: &Path) -> io::Result<()> {
+        match self.format {
+            // The table of contents already lists every file's full path.
+            OutputFormat::Plain | OutputFormat::Markdown if self.toc => {}
+            OutputFormat::Plain => {
+                writeln!(self.sink, "## Root: {}", root.display())?;
+                writeln!(self.sink)?;
+            }
+            OutputFormat::Markdown => {
+                writeln!(self.sink, "# {}", root.display())?;
+                writeln!(self.sink)?;
+            }
+            // Structured formats list the roots in the document itself.
+            OutputFormat::Json | OutputFormat::Xml => {}
+        }
+        Ok(())
+    }
+
+    /// Gives the git diff printer access to the same sink as the file listing.
+    pub fn writer(&mut self) -> &mut dyn Write {
+        &mut self.sink
+    }
+
+    /// Flushes any buffered output. Plain output is streamed, so this only
+    /// matters for formats that need the whole walk before they can render.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(ref stats) = self.stats {
+            writeln!(
+                self.sink,
+                "Total: {} {}, {} {}, {}",
+                stats.files,
+                plural(stats.files, "file", "files"),
+                stats.lines,
+                plural(stats.lines, "line", "lines"),
+                format_size(stats.bytes)
+            )?;
+            write_breakdown(&mut self.sink, "By language", &stats.by_language)?;
+            write_breakdown(&mut self.sink, "By directory", &stats.by_directory)?;
+            write_largest_files(&mut self.sink, &stats.sizes, stats.top)?;
+        }
+        if let Some(ref tally) = self.match_tally {
+            // Structured formats carry the per-file results themselves.
+            if matches!(self.format, OutputFormat::Plain | OutputFormat::Markdown) {
+                writeln!(
+                    self.sink,
+                    "Pattern matches: {} across {} {}",
+                    tally.matches,
+                    tally.files,
+                    plural(tally.files, "file", "files")
+                )?;
+            }
+        }
+
+        match self.format {
+            OutputFormat::Json => {
+                let json = self.render_json()?;
+                writeln!(self.sink, "{}", json)?;
+            }
+            OutputFormat::Xml => {
+                let xml = self.render_xml();
+                write!(self.sink, "{}", xml)?;
+            }
+            OutputFormat::Plain | OutputFormat::Markdown if self.toc => self.render_toc()?,
+            OutputFormat::Plain | OutputFormat::Markdown => {}
+        }
+        self.sink.flush()?;
+        Ok(())
+    }
+
+    fn render_toc(&mut self) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Markdown => writeln!(self.sink, "## Table of Contents")?,
+            _ => writeln!(self.sink, "### Table of Contents")?,
+        }
+        writeln!(self.sink)?;
+        for (i, file) in self.files.iter().enumerate() {
+            let lines = file.contents.lines().count();
+            writeln!(
+                self.sink,
+                "{}. {} ({} {}, {})",
+                i + 1,
+                file.path,
+                lines,
+                plural(lines, "line", "lines"),
+                format_size(file.contents.len())
+            )?;
+        }
+        writeln!(self.sink)?;
+
+        for file in &self.files {
+            write_file_section(
+                &mut self.sink,
+                self.format,
+                Path::new(&file.path),
+                &file.contents,
+                file.annotation.as_deref(),
+                file.omitted_lines.unwrap_or(0),
+                self.collapsible,
+                self.wrap,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn render_xml(&self) -> String {
+        let mut out = String::from("<documents>\n");
+        for (i, file) in self.files.iter().enumerate() {
+            out.push_str(&format!("<document index=\"{}\">\n", i + 1));
+            out.push_str(&format!("<source>{}</source>\n", escape_xml(&file.path)));
+            if let Some(ref annotation) = file.annotation {
+                out.push_str(&format!(
+                    "<annotation>{}</annotation>\n",
+                    escape_xml(annotation)
+                ));
+            }
+            out.push_str(&format!(
+                "<document_contents>\n{}\n",
+                escape_xml(file.contents.trim_end_matches('\n'))
+            ));
+            if let Some(omitted) = file.omitted_lines {
+                out.push_str(&omitted_note(omitted));
+            }
+            out.push_str("</document_contents>\n");
+            out.push_str("</document>\n");
+        }
+        out.push_str("</documents>\n");
+        out
+    }
+
+    fn render_json(&self) -> Result<String, serde_json::Error> {
+        let repository = Path::new(&self.roots[0])
+            .canonicalize()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| self.roots[0].clone());
+
+        serde_json::to_string_pretty(&JsonSnapshot {
+            repository,
+            roots: &self.roots,
+            filters: &self.filters,
+            total_files: self.files.len(),
+            files: &self.files,
+        })
+    }
+}
+
+/// Writes a `--stats-only` breakdown such as `By language: rust: 2 files, 40
+/// lines; …`, largest bucket first. Nothing is written for an empty map.
+fn write_breakdown(
+    sink: &mut dyn Write,
+    title: &str,
+    buckets: &BTreeMap<String, (usize, usize)>,
+) -> io::Result<()> {
+    if buckets.is_empty() {
+        return Ok(());
+    }
+    let mut buckets: Vec<_> = buckets.iter().collect();
+    // Largest first; the map already orders ties by name.
+    buckets.sort_by_key(|&(_, &(_, lines))| Reverse(lines));
+    let parts: Vec<String> = buckets
+        .into_iter()
+        .map(|(name, &(files, lines))| {
+            format!(
+                "{}: {} {}, {} {}",
+                name,
+                files,
+                plural(files, "file", "files"),
+                lines,
+                plural(lines, "line", "lines")
+            )
+        })
+        .collect();
+    writeln!(sink, "{}: {}", title, parts.join("; "))
+}
+
+/// Writes the `top` entries of `sizes` with the most lines as `Largest files:
+/// big.rs (120 lines, 3.1 KiB); …`. Nothing is written if `top` is 0 or no
+/// file was listed.
+fn write_largest_files(
+    sink: &mut dyn Write,
+    sizes: &[(String, usize, usize)],
+    top: usize,
+) -> io::Result<()> {
+    if top == 0 || sizes.is_empty() {
+        return Ok(());
+    }
+    let mut sizes: Vec<_> = sizes.iter().collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let parts: Vec<String> = sizes
+        .into_iter()
+        .take(top)
+        .map(|(path, lines, bytes)| {
+            format!(
+                "{} ({} {}, {})",
+                path,
+                lines,
+                plural(*lines, "line", "lines"),
+                format_size(*bytes)
+            )
+        })
+        .collect();
+    writeln!(sink, "Largest files: {}", parts.join("; "))
+}
+
+/// Writes one file as a plain or Markdown section. `collapsible` Markdown
+/// sections replace the heading with a `<details>` summary.
+#[allow(clippy::too_many_arguments)]
+fn write_file_section(
+    sink: &mut dyn Write,
+    format: OutputFormat,
+    path: &Path,
+    contents: &str,
+    annotation: Option<&str>,
+    omitted_lines: usize,
+    collapsible: bool,
+    wrap: Option<usize>,
+) -> io::Result<()> {
+    let mut body = match wrap {
+        Some(width) => Cow::Owned(wrap_lines(contents, width)),
+        None => Cow::Borrowed(contents),
+    };
+    if omitted_lines > 0 {
+        body = Cow::Owned(format!("{}{}", body, omitted_note(omitted_lines)));
+    }
+    let lines = contents.lines().count();
+    if format == OutputFormat::Markdown {
+        if collapsible {
+            writeln!(sink, "<details>")?;
+            writeln!(
+                sink,
+                "<summary>{} ({} {}, {})</summary>",
+                escape_xml(&path.display().to_string()),
+                lines,
+                plural(lines, "line", "lines"),
+                format_size(contents.len())
+            )?;
+        } else {
+            writeln!(sink, "## {}", path.display())?;
+        }
+        writeln!(sink)?;
+        if let Some(annotation) = annotation {
+            writeln!(sink, "_{}_", annotation)?;
+            writeln!(sink)?;
+        }
+        writeln!(sink, "```{}", fence_language(path))?;
+        writeln!(sink, "{}", body.trim_end_matches('\n'))?;
+        if collapsible {
+            writeln!(sink, "```")?;
+            writeln!(sink)?;
+            writeln!(sink, "</details>")?;
+            return writeln!(sink);
+        }
+    } else {
+        write!(
+            sink,
+            "### File: {} ({} {}, {}",
+            path.display(),
+            lines,
+            plural(lines, "line", "lines"),
+            format_size(contents.len())
+        )?;
+        match annotation {
+            Some(annotation) => writeln!(sink, ", {})", annotation)?,
+            None => writeln!(sink, ")")?,
+        }
+        writeln!(sink, "```")?;
+        writeln!(sink, "{}", body)?;
+    }
+    writeln!(sink, "```")?;
+    writeln!(sink)
+}
+
+/// Splits every line of `contents` longer than `width` characters into
+/// `width`-character pieces.
+fn wrap_lines(contents: &str, width: usize) -> String {
+    let mut out = String::with_capacity(contents.len());
+    for line in contents.split_inclusive('\n') {
+        let (text, ending) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let chars: Vec<char> = text.chars().collect();
+        for (i, piece) in chars.chunks(width.max(1)).enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.extend(piece);
+        }
+        out.push_str(ending);
+    }
+    out
+}
+
+/// Picks the singular or plural form of a noun for a count of `n`.
+pub(crate) fn plural(n: usize, one: &'static str, many: &'static str) -> &'static str {
+    if n == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// The line closing a file cut short by `--head`.
+fn omitted_note(omitted_lines: usize) -> String {
+    format!(
+        "… ({} more {} omitted)\n",
+        omitted_lines,
+        plural(omitted_lines, "line", "lines")
+    )
+}
+
+/// Renders a byte count for humans, e.g. `812 B` or `3.1 KB`.
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// The `By language` bucket for a file: its [`Language`], else its
+/// extension, else `other`.
+fn language_label(path: &Path) -> String {
+    match path.extension().and_then(|os_str| os_str.to_str()) {
+        Some(extension) => Language::from_extension(extension)
+            .map_or_else(|| extension.to_lowercase(), Language::name),
+        None => "other".to_string(),
+    }
+}
+
+/// The `By directory` bucket for a file: the first `depth` directories of
+/// its path joined with `/`, or `.` for a file outside any directory.
+pub(crate) fn directory_label(path: &Path, depth: usize) -> String {
+    let directories: Vec<String> = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .take(depth)
+        .collect();
+    if directories.is_empty() {
+        ".".to_string()
+    } else {
+        directories.join("/")
+    }
+}
+
+/// Maps a file extension to the info string used on a Markdown code fence.
+/// JSX and TSX keep their own tags, which highlighters know; other files are
+/// tagged by their [`Language`].
+fn fence_language(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|os_str| os_str.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match extension.as_str() {
+        "jsx" => "jsx",
+        "tsx" => "tsx",
+        _ => Language::from_extension(&extension).map_or("", Language::fence_tag),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,56 @@
+use crate::snapshot::RunReport;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// One line of a [`Manifest`]: a printed file and a hash of its contents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    /// The hex SHA-256 of the file's bytes on disk, as printed by
+    /// `sha256sum`.
+    pub hash: String,
+    /// The size of the same bytes the hash covers.
+    pub bytes: usize,
+    pub lines: usize,
+}
+
+/// A machine-readable list of the files a run printed, written by
+/// `--manifest` for caching and change detection.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Lists the printed files and those `--since-manifest` skipped as
+    /// unchanged, sorted by path, so a manifest can be refreshed in place.
+    pub fn from_report(report: &RunReport) -> Self {
+        let mut files: Vec<ManifestEntry> = report
+            .files
+            .iter()
+            .chain(&report.unchanged)
+            .map(|file| ManifestEntry {
+                path: file.printed_as.display().to_string(),
+                hash: file.hash.clone(),
+                bytes: file.bytes,
+                lines: file.lines,
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Manifest { files }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| format!("Invalid manifest {}: {}", path.display(), e).into())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .map_err(|e| format!("Failed to write manifest {}: {}", path.display(), e).into())
+    }
+}
@@ -0,0 +1,3 @@
+pub mod formatter;
+pub mod manifest;
+pub mod summary;
@@ -0,0 +1,79 @@
+use crate::output::formatter::directory_label;
+use crate::snapshot::{active_filters, RunReport};
+use crate::{Args, DiffStat, Filters};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A printed file's counts in a [`Summary`]. Like the `--stats-only` totals,
+/// they cover only the part of the file that was printed.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SummaryFile {
+    pub path: String,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+/// The files, lines and bytes printed from one directory bucket.
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirectoryTotals {
+    pub files: usize,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+/// Machine-readable totals for a run, written by `--summary-json` next to
+/// the human-readable output so CI can check the size of a snapshot.
+#[derive(Serialize, Debug, Clone)]
+pub struct Summary {
+    pub total_files: usize,
+    pub total_lines: usize,
+    pub total_bytes: usize,
+    pub files: Vec<SummaryFile>,
+    /// Totals keyed by the first `--group-depth` directories of each printed
+    /// path, as in the `--stats-only` breakdown.
+    pub directories: BTreeMap<String, DirectoryTotals>,
+    pub filters: Filters,
+    /// Only set in the git and `--diff-path` diff modes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_stat: Option<DiffStat>,
+}
+
+impl Summary {
+    pub fn from_report(report: &RunReport, args: &Args) -> Self {
+        let mut directories: BTreeMap<String, DirectoryTotals> = BTreeMap::new();
+        for file in &report.files {
+            let totals = directories
+                .entry(directory_label(&file.printed_as, args.group_depth as usize))
+                .or_default();
+            totals.files += 1;
+            totals.lines += file.printed_lines;
+            totals.bytes += file.printed_bytes;
+        }
+
+        Summary {
+            total_files: report.files.len(),
+            total_lines: report.files.iter().map(|file| file.printed_lines).sum(),
+            total_bytes: report.files.iter().map(|file| file.printed_bytes).sum(),
+            files: report
+                .files
+                .iter()
+                .map(|file| SummaryFile {
+                    path: file.printed_as.display().to_string(),
+                    lines: file.printed_lines,
+                    bytes: file.printed_bytes,
+                })
+                .collect(),
+            directories,
+            filters: active_filters(args),
+            diff_stat: report.diff_stat,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .map_err(|e| format!("Failed to write summary {}: {}", path.display(), e).into())
+    }
+}
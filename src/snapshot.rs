@@ -0,0 +1,1457 @@
+use crate::args::{ChangeType, SortOrder};
+use crate::detect_renames;
+use crate::diff_trees;
+use crate::empty_tree;
+use crate::file_extension_matches;
+use crate::file_utils::encoding::decode_text;
+use crate::find_revision;
+use crate::find_revision_or_empty;
+use crate::find_tree;
+use crate::is_binary_blob;
+use crate::is_likely_binary;
+use crate::last_changes;
+use crate::open_repo;
+use crate::read_text_file;
+use crate::Args;
+use crate::BinaryExtensions;
+use crate::Manifest;
+use crate::Rename;
+use crate::{blob_data, diff_tree_to_worktree, untracked_additions, WorktreeBlobs, WORKTREE};
+use crate::{count_line_changes, unified_diff, DiffStat};
+use crate::{Filters, OutputFormat, OutputFormatter};
+use gix::bstr::BString;
+use gix::bstr::ByteSlice;
+use gix::diff::tree::recorder::Change;
+use gix::Repository;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Prints a diagnostic to stderr once `-v` has been given at least `$level`
+/// times: `-v` for run-level facts such as resolved revisions and file
+/// counts, `-vv` for the reason each file was skipped.
+macro_rules! verbose {
+    ($args:expr, $level:expr, $($arg:tt)*) => {
+        if $args.verbose >= $level {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a warning about a single file to stderr unless `--quiet` is set.
+/// Errors that abort the run are returned instead and always reported.
+macro_rules! warn {
+    ($quiet:expr, $($arg:tt)*) => {
+        if !$quiet {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+struct GitPath(PathBuf);
+
+impl From<&BString> for GitPath {
+    fn from(bstring: &BString) -> Self {
+        GitPath(PathBuf::from(bstring.to_path_lossy()))
+    }
+}
+
+impl AsRef<Path> for GitPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+/// Filters applied to each walked file, or to each side of a git change,
+/// before its content is printed.
+struct FileFilters {
+    extensions: Option<Vec<String>>,
+    binary_extensions: BinaryExtensions,
+    pattern: Option<Regex>,
+    excludes: Option<Vec<Regex>>,
+    includes: Option<GlobSet>,
+    max_file_size: Option<u64>,
+    /// Keep only the files that `pattern` never matches.
+    invert_match: bool,
+    /// Keep the per-file skip warnings off stderr (`--quiet`).
+    quiet: bool,
+    /// Content hashes by printed path from `--since-manifest`; files whose
+    /// hash is unchanged are skipped.
+    previous_hashes: Option<HashMap<String, String>>,
+}
+
+/// A file that was printed, with its size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    pub path: PathBuf,
+    /// The path shown in the output, relative to its root by default.
+    pub printed_as: PathBuf,
+    pub lines: usize,
+    /// The file's size on disk, before any decoding.
+    pub bytes: usize,
+    /// The lines and bytes actually printed, after `--head` and
+    /// `--ignore-blank-lines`, as counted in the `--stats-only` totals.
+    pub printed_lines: usize,
+    pub printed_bytes: usize,
+    /// The hex SHA-256 of the file's bytes as stored on disk.
+    pub hash: String,
+}
+
+/// What a [`run`] printed and skipped. `diff_stat` is only set in git diff
+/// mode, where `files` and `skipped` stay empty.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RunReport {
+    pub files: Vec<FileReport>,
+    /// Files skipped by `--since-manifest` because their hash matched, kept
+    /// so that a new `--manifest` still lists them.
+    pub unchanged: Vec<FileReport>,
+    pub skipped: SkipCounts,
+    pub diff_stat: Option<DiffStat>,
+}
+
+/// Walks the paths in `args`, or diffs the revisions it names, and prints
+/// everything through `formatter`. The caller is expected to call
+/// [`OutputFormatter::finish`] afterwards; the returned report describes what
+/// was printed and skipped.
+pub fn run(
+    args: &Args,
+    formatter: &mut OutputFormatter<impl Write>,
+) -> Result<RunReport, Box<dyn std::error::Error>> {
+    let is_git_diff = args.git_from.is_some()
+        || args.git_to.is_some()
+        || args.commit.is_some()
+        || args.since.is_some();
+    if matches!(args.format, OutputFormat::Json | OutputFormat::Xml) && is_git_diff {
+        return Err("--format json/xml is not supported with --git-from/--git-to".into());
+    }
+    if matches!(args.format, OutputFormat::Json | OutputFormat::Xml) && args.diff_path.is_some() {
+        return Err("--format json/xml is not supported with --diff-path".into());
+    }
+    if matches!(args.format, OutputFormat::Json | OutputFormat::Xml)
+        && (args.stats_only || args.dry_run || args.files_with_matches)
+    {
+        return Err(
+            "--format json/xml is not supported with --stats-only, --dry-run or --files-with-matches"
+                .into(),
+        );
+    }
+
+    if args.collapsible && args.format != OutputFormat::Markdown {
+        return Err("--collapsible requires --format markdown".into());
+    }
+
+    // Compile every pattern before producing any output so a typo fails fast.
+    let filters = FileFilters {
+        pattern: args
+            .pattern
+            .as_deref()
+            .map(|p| compile_regex("--pattern", p, args.ignore_case))
+            .transpose()?,
+        extensions: extension_filter(args),
+        binary_extensions: BinaryExtensions::new(&args.binary_extensions, &args.text_extensions),
+        excludes: args
+            .excludes
+            .as_ref()
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .map(|p| compile_regex("--excludes", p, false))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?,
+        includes: build_includes(args.includes.as_deref())?,
+        max_file_size: args.max_file_size,
+        invert_match: args.invert_match,
+        quiet: args.quiet,
+        previous_hashes: args
+            .since_manifest
+            .as_deref()
+            .map(Manifest::read)
+            .transpose()?
+            .map(|manifest| {
+                manifest
+                    .files
+                    .into_iter()
+                    .map(|entry| (entry.path, entry.hash))
+                    .collect()
+            }),
+    };
+
+    if is_git_diff {
+        if args.path.len() != 1 {
+            return Err("--git-from/--git-to require a single --path".into());
+        }
+        let stat = print_git_diff(args, &filters, formatter)?;
+        return Ok(RunReport {
+            diff_stat: Some(stat),
+            ..RunReport::default()
+        });
+    }
+
+    if let Some(ref other) = args.diff_path {
+        if args.path.len() != 1 {
+            return Err("--diff-path requires a single --path".into());
+        }
+        let stat = print_path_diff(args, &filters, other, formatter)?;
+        return Ok(RunReport {
+            diff_stat: Some(stat),
+            ..RunReport::default()
+        });
+    }
+
+    let mut report = RunReport::default();
+    let mut progress = Progress::new(args.progress);
+    if let Some(ref list) = args.files_from {
+        let paths = read_file_list(list)?;
+        let annotations = blame_annotations(args, &paths);
+        for path in paths {
+            process_file(
+                formatter,
+                &path,
+                &path,
+                &filters,
+                args,
+                &annotations,
+                &mut report,
+            )?;
+            progress.tick(&report);
+        }
+    } else {
+        for root in &args.path {
+            if args.path.len() > 1 {
+                formatter.print_root_header(root)?;
+            }
+            // Only paths are buffered, so sorting costs little memory even
+            // for large trees; contents are still read one file at a time.
+            let mut files = Vec::new();
+            for result in walk_builder(args, root)?.build() {
+                match result {
+                    Ok(entry) => {
+                        if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                            files.push(entry.into_path());
+                        }
+                    }
+                    Err(e) => warn!(args.quiet, "Error: {}", e),
+                }
+            }
+            sort_files(&mut files, args.sort);
+            verbose!(
+                args,
+                1,
+                "Walked {} file(s) under {}",
+                files.len(),
+                root.display()
+            );
+
+            let annotations = blame_annotations(args, &files);
+            for path in &files {
+                process_file(
+                    formatter,
+                    path,
+                    path.strip_prefix(root).unwrap_or(path),
+                    &filters,
+                    args,
+                    &annotations,
+                    &mut report,
+                )?;
+                progress.tick(&report);
+            }
+        }
+    }
+    progress.clear();
+    verbose!(
+        args,
+        1,
+        "{} file(s) passed the filters, {} skipped",
+        report.files.len(),
+        report.skipped.total()
+    );
+
+    Ok(report)
+}
+
+/// The filters in `args` as recorded in JSON output and `--summary-json`.
+pub(crate) fn active_filters(args: &Args) -> Filters {
+    Filters {
+        extensions: extension_filter(args),
+        excludes: args.excludes.clone(),
+        includes: args.includes.clone(),
+    }
+}
+
+/// Creates the formatter `run` expects for `args`: every root registered,
+/// the active filters recorded and the listing mode selected.
+pub fn formatter_for<W: Write>(args: &Args, sink: W) -> OutputFormatter<W> {
+    let first_root = args.path.first().map_or(Path::new("."), |p| p.as_path());
+    let mut formatter = OutputFormatter::new(args.format, first_root)
+        .with_sink(sink)
+        .with_filters(active_filters(args));
+    if args.stats_only {
+        formatter = formatter.with_stats_only();
+    } else if args.dry_run {
+        formatter = formatter.with_dry_run();
+    } else if args.toc {
+        formatter = formatter.with_toc();
+    }
+    if args.collapsible {
+        formatter = formatter.with_collapsible();
+    }
+    formatter = formatter.with_group_depth(args.group_depth as usize);
+    formatter = formatter.with_top(args.top);
+    if let Some(width) = args.wrap {
+        formatter = formatter.with_wrap(usize::from(width));
+    }
+    for root in args.path.iter().skip(1) {
+        formatter.add_root(root);
+    }
+
+    formatter
+}
+
+/// A single self-overwriting status line on stderr for `--progress`, so
+/// stdout stays clean for the snapshot.
+struct Progress {
+    enabled: bool,
+    last_update: Option<Instant>,
+}
+
+impl Progress {
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn new(enabled: bool) -> Self {
+        Progress {
+            enabled,
+            last_update: None,
+        }
+    }
+
+    fn tick(&mut self, report: &RunReport) {
+        if !self.enabled
+            || self
+                .last_update
+                .is_some_and(|t| t.elapsed() < Self::INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(Instant::now());
+
+        let printed = report.files.len();
+        let bytes: usize = report.files.iter().map(|file| file.printed_bytes).sum();
+        eprint!(
+            "\r\x1b[2KProcessed {} files: {} printed ({} bytes), {} skipped",
+            printed + report.skipped.total(),
+            printed,
+            bytes,
+            report.skipped.total()
+        );
+    }
+
+    fn clear(&self) {
+        if self.enabled && self.last_update.is_some() {
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+/// Files dropped by the walk, by reason.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SkipCounts {
+    pub extension: usize,
+    pub not_included: usize,
+    pub too_large: usize,
+    pub binary: usize,
+    pub excluded: usize,
+    /// Empty, BOM-only or whitespace-only files.
+    pub empty: usize,
+    /// Files whose lines are too long on average to be hand-written.
+    pub minified: usize,
+    /// Files whose hash matches the `--since-manifest` entry.
+    pub unchanged: usize,
+    pub undecodable: usize,
+    pub read_errors: usize,
+}
+
+impl SkipCounts {
+    pub fn total(&self) -> usize {
+        self.extension
+            + self.not_included
+            + self.too_large
+            + self.binary
+            + self.excluded
+            + self.empty
+            + self.minified
+            + self.unchanged
+            + self.undecodable
+            + self.read_errors
+    }
+}
+
+impl std::fmt::Display for SkipCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reasons = [
+            ("extension filtered", self.extension),
+            ("not included", self.not_included),
+            ("too large", self.too_large),
+            ("binary", self.binary),
+            ("excluded", self.excluded),
+            ("empty", self.empty),
+            ("minified", self.minified),
+            ("unchanged", self.unchanged),
+            ("undecodable", self.undecodable),
+            ("read errors", self.read_errors),
+        ];
+        let parts: Vec<String> = reasons
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(reason, count)| format!("{}: {}", reason, count))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Whether the average line of `contents` is longer than `max_line_length`,
+/// as in minified bundles and generated data. `0` disables the check.
+fn looks_minified(contents: &str, max_line_length: usize) -> bool {
+    if max_line_length == 0 {
+        return false;
+    }
+    let lines = contents.lines().count().max(1);
+    contents.len() / lines > max_line_length
+}
+
+/// The part of a file that is printed in full: without whitespace-only lines
+/// under `--ignore-blank-lines`, then cut short by `--head`. Line and size
+/// counts are taken from the result. Also returns the number of lines
+/// `--head` dropped.
+fn printed_contents<'a>(contents: &'a str, args: &Args) -> (Cow<'a, str>, usize) {
+    if !args.ignore_blank_lines {
+        return head_lines(contents, args.head);
+    }
+    let kept: String = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    let (head, omitted) = head_lines(&kept, args.head);
+    (Cow::Owned(head.into_owned()), omitted)
+}
+
+/// Cuts `contents` down to its first `head` lines for `--head`, and returns
+/// them with the number of lines that were dropped.
+fn head_lines(contents: &str, head: Option<usize>) -> (Cow<'_, str>, usize) {
+    let Some(head) = head else {
+        return (Cow::Borrowed(contents), 0);
+    };
+    let total = contents.lines().count();
+    if total <= head {
+        return (Cow::Borrowed(contents), 0);
+    }
+
+    let mut kept = String::new();
+    for line in contents.lines().take(head) {
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    (Cow::Owned(kept), total - head)
+}
+
+/// Describes the last commit that touched each of `paths` for `--blame`,
+/// keyed by the path as given. Every path is looked up in the repository it
+/// belongs to, and each repository's history is walked once for all of its
+/// paths. Untracked files and files outside any work tree get no entry, and
+/// nothing is looked up without `--blame`.
+fn blame_annotations(args: &Args, paths: &[PathBuf]) -> HashMap<PathBuf, String> {
+    let mut annotations = HashMap::new();
+    if !args.blame {
+        return annotations;
+    }
+
+    // The work tree each parent directory belongs to, and for each work tree
+    // its repository and the (given, repo-relative) paths to look up.
+    let mut repos: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+    let mut by_workdir: HashMap<PathBuf, (Repository, Vec<(PathBuf, PathBuf)>)> = HashMap::new();
+    for path in paths {
+        let Ok(canonical) = path.canonicalize() else {
+            continue;
+        };
+        let dir = canonical.parent().unwrap_or(&canonical).to_path_buf();
+        let workdir = repos.entry(dir.clone()).or_insert_with(|| {
+            let repo = open_repo(&dir).ok()?;
+            let workdir = repo.work_dir()?.canonicalize().ok()?;
+            by_workdir
+                .entry(workdir.clone())
+                .or_insert_with(|| (repo, Vec::new()));
+            Some(workdir)
+        });
+        let Some(workdir) = workdir else {
+            continue;
+        };
+        if let Ok(relative) = canonical.strip_prefix(&*workdir) {
+            if let Some((_, wanted)) = by_workdir.get_mut(&*workdir) {
+                wanted.push((path.clone(), relative.to_path_buf()));
+            }
+        }
+    }
+
+    for (workdir, (repo, wanted)) in by_workdir {
+        let relatives: Vec<PathBuf> = wanted
+            .iter()
+            .map(|(_, relative)| relative.clone())
+            .collect();
+        match last_changes(&repo, &relatives) {
+            Ok(changes) => {
+                for (path, relative) in wanted {
+                    if let Some(change) = changes.get(&relative) {
+                        annotations.insert(path, change.to_string());
+                    }
+                }
+            }
+            Err(e) => warn!(
+                args.quiet,
+                "Error reading history of {}: {}",
+                workdir.display(),
+                e
+            ),
+        }
+    }
+    annotations
+}
+
+/// Runs one candidate file through the filter chain and prints it if it
+/// passes. `relative` is the path that `--include` globs are matched against,
+/// and the one printed unless `--absolute-paths` is set. `annotations` holds
+/// the `--blame` notes from [`blame_annotations`].
+fn process_file(
+    formatter: &mut OutputFormatter<impl Write>,
+    path: &Path,
+    relative: &Path,
+    filters: &FileFilters,
+    args: &Args,
+    annotations: &HashMap<PathBuf, String>,
+    report: &mut RunReport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let skipped = &mut report.skipped;
+    if let Some(ref exts) = filters.extensions {
+        if !file_extension_matches(path, exts) {
+            verbose!(args, 2, "Skipped (not in --extensions): {}", path.display());
+            skipped.extension += 1;
+            return Ok(());
+        }
+    }
+
+    if let Some(ref globs) = filters.includes {
+        if !globs.is_match(relative) {
+            verbose!(
+                args,
+                2,
+                "Skipped (not matched by --include): {}",
+                path.display()
+            );
+            skipped.not_included += 1;
+            return Ok(());
+        }
+    }
+
+    if let Some(max_size) = filters.max_file_size {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if size > max_size {
+            warn!(
+                filters.quiet,
+                "Skipping large file ({} bytes): {}",
+                size,
+                path.display()
+            );
+            skipped.too_large += 1;
+            return Ok(());
+        }
+    }
+
+    if is_likely_binary(path, &filters.binary_extensions) {
+        verbose!(args, 2, "Skipped (binary): {}", path.display());
+        skipped.binary += 1;
+        return Ok(());
+    }
+
+    if let Some(ref regexes) = filters.excludes {
+        if regexes
+            .iter()
+            .any(|re| re.is_match(path.to_str().unwrap_or("")))
+        {
+            verbose!(
+                args,
+                2,
+                "Skipped (matched by --excludes): {}",
+                path.display()
+            );
+            skipped.excluded += 1;
+            return Ok(());
+        }
+    }
+
+    // A --path naming a single file has an empty relative path.
+    let shown = if args.absolute_paths || relative.as_os_str().is_empty() {
+        path
+    } else {
+        relative
+    };
+
+    // The raw bytes are kept for the manifest hash.
+    let read = fs::read(path).map(|bytes| {
+        let contents = decode_text(&bytes);
+        (bytes, contents)
+    });
+    match read {
+        Ok((bytes, Some(contents))) => {
+            // Decoding already dropped any byte order mark.
+            if contents.trim().is_empty() {
+                verbose!(args, 2, "Skipped (empty): {}", path.display());
+                skipped.empty += 1;
+                return Ok(());
+            }
+            if looks_minified(&contents, args.max_line_length) {
+                verbose!(args, 2, "Skipped (minified): {}", path.display());
+                skipped.minified += 1;
+                return Ok(());
+            }
+            let mut file_report = FileReport {
+                path: path.to_path_buf(),
+                printed_as: shown.to_path_buf(),
+                lines: contents.lines().count(),
+                bytes: bytes.len(),
+                printed_lines: 0,
+                printed_bytes: 0,
+                hash: hex::encode(Sha256::digest(&bytes)),
+            };
+            if let Some(ref previous) = filters.previous_hashes {
+                let key = shown.display().to_string();
+                if previous.get(&key) == Some(&file_report.hash) {
+                    verbose!(args, 2, "Skipped (unchanged): {}", path.display());
+                    skipped.unchanged += 1;
+                    report.unchanged.push(file_report);
+                    return Ok(());
+                }
+            }
+            if let Some(ref regex) = filters.pattern {
+                if filters.invert_match || args.files_with_matches {
+                    let matched = contents.lines().any(|line| regex.is_match(line));
+                    if matched == filters.invert_match {
+                        verbose!(args, 2, "Skipped (--pattern filter): {}", path.display());
+                        return Ok(());
+                    }
+                    if args.files_with_matches {
+                        formatter.print_path(shown)?;
+                    } else {
+                        let annotation = annotations.get(path).map(String::as_str);
+                        let (printed, omitted) = printed_contents(&contents, args);
+                        formatter.print_file_head(shown, &printed, annotation, omitted)?;
+                        file_report.printed_lines = printed.lines().count();
+                        file_report.printed_bytes = printed.len();
+                    }
+                } else {
+                    let (rendered, matches) = render_matches_with_context(
+                        &contents,
+                        regex,
+                        args.context_lines,
+                        !args.no_line_numbers,
+                    );
+                    formatter.print_match_block(shown, &rendered, matches)?;
+                    file_report.printed_lines = rendered.lines().count();
+                    file_report.printed_bytes = rendered.len();
+                }
+            } else {
+                let annotation = annotations.get(path).map(String::as_str);
+                let (printed, omitted) = printed_contents(&contents, args);
+                formatter.print_file_head(shown, &printed, annotation, omitted)?;
+                file_report.printed_lines = printed.lines().count();
+                file_report.printed_bytes = printed.len();
+            }
+            report.files.push(file_report);
+        }
+        Ok((_, None)) => {
+            warn!(args.quiet, "Skipping undecodable file: {}", path.display());
+            skipped.undecodable += 1;
+        }
+        Err(e) => {
+            warn!(args.quiet, "Error reading file {}: {}", path.display(), e);
+            skipped.read_errors += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads newline-separated paths from a file, or from stdin when `list` is `-`.
+fn read_file_list(list: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let contents = if list == Path::new("-") {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(list)
+            .map_err(|e| format!("Failed to read file list {}: {}", list.display(), e))?
+    };
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Builds a line of `^` markers under every match of `regex` in `line`.
+/// Tabs are copied into the padding so the markers stay aligned.
+fn match_underline(line: &str, regex: &Regex) -> String {
+    let mut underline = String::new();
+    let mut column = 0;
+    for found in regex.find_iter(line) {
+        for c in line[column..found.start()].chars() {
+            underline.push(if c == '\t' { '\t' } else { ' ' });
+        }
+        let width = found.as_str().chars().count().max(1);
+        underline.push_str(&"^".repeat(width));
+        column = found.end();
+    }
+    underline
+}
+
+/// Combines `--extensions` with the extensions of every `--languages` entry.
+fn extension_filter(args: &Args) -> Option<Vec<String>> {
+    if args.extensions.is_none() && args.languages.is_none() {
+        return None;
+    }
+
+    let mut extensions: Vec<String> = args
+        .extensions
+        .iter()
+        .flatten()
+        .map(|e| e.to_lowercase())
+        .collect();
+    for language in args.languages.iter().flatten() {
+        for ext in language.extensions() {
+            if !extensions.iter().any(|e| e == ext) {
+                extensions.push(ext.to_string());
+            }
+        }
+    }
+    Some(extensions)
+}
+
+fn sort_files(files: &mut [PathBuf], order: SortOrder) {
+    match order {
+        SortOrder::Path => files.sort(),
+        SortOrder::Size => {
+            files.sort_by_cached_key(|path| {
+                let size = fs::metadata(path).map_or(0, |m| m.len());
+                (std::cmp::Reverse(size), path.clone())
+            });
+        }
+    }
+}
+
+fn compile_regex(flag: &str, pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| format!("invalid regex for {}: {}", flag, e))
+}
+
+fn build_includes(
+    patterns: Option<&[String]>,
+) -> Result<Option<GlobSet>, Box<dyn std::error::Error>> {
+    let Some(patterns) = patterns else {
+        return Ok(None);
+    };
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| format!("Invalid --include glob '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Configures the directory walk from the CLI flags, so every walk honours the
+/// same ignore rules.
+fn walk_builder(args: &Args, root: &Path) -> Result<WalkBuilder, Box<dyn std::error::Error>> {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(args.skip_hidden)
+        .git_ignore(!args.no_gitignore)
+        .git_global(!args.no_gitignore)
+        .git_exclude(!args.no_gitignore)
+        // The walker reports symlink cycles as errors instead of looping.
+        .follow_links(args.follow_symlinks)
+        .max_depth(args.max_depth.map(|depth| depth as usize))
+        // Project-local excludes for snapshots only, independent of git.
+        .add_custom_ignore_filename(".repowalkerignore");
+
+    if let Some(ref names) = args.exclude_dirs {
+        // Filtering entries here prunes whole subtrees instead of walking them.
+        let names = names.clone();
+        builder.filter_entry(move |entry| {
+            !(entry.file_type().is_some_and(|ft| ft.is_dir())
+                && entry.depth() > 0
+                && names.iter().any(|name| entry.file_name() == name.as_str()))
+        });
+    }
+
+    for ignore_file in args.ignore_files.iter().flatten() {
+        if let Some(e) = builder.add_ignore(ignore_file) {
+            return Err(format!(
+                "Failed to load ignore file {}: {}",
+                ignore_file.display(),
+                e
+            )
+            .into());
+        }
+    }
+
+    Ok(builder)
+}
+
+/// A run of output lines holding one or more matches and their context.
+struct MatchBlock<'a> {
+    start: usize,
+    end: usize,
+    matches: Vec<(usize, regex::Captures<'a>)>,
+}
+
+/// Renders every match with its surrounding context. Matches whose context
+/// windows overlap or touch are merged into a single block, and separate
+/// blocks are divided by a `--` line, as grep does. Also returns the number
+/// of matching lines. Without `line_numbers`, lines keep only the `>` marker
+/// so they can be copied back out verbatim.
+fn render_matches_with_context(
+    contents: &str,
+    regex: &Regex,
+    context_lines: usize,
+    line_numbers: bool,
+) -> (String, usize) {
+    let lines: Vec<&str> = contents.lines().collect();
+
+    let mut blocks: Vec<MatchBlock> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(captures) = regex.captures(line) else {
+            continue;
+        };
+        let start = i.saturating_sub(context_lines);
+        let end = (i + context_lines + 1).min(lines.len());
+        match blocks.last_mut() {
+            Some(block) if start <= block.end => {
+                block.end = end;
+                block.matches.push((i, captures));
+            }
+            _ => blocks.push(MatchBlock {
+                start,
+                end,
+                matches: vec![(i, captures)],
+            }),
+        }
+    }
+
+    if blocks.is_empty() {
+        return (String::from("No matches found in this file.\n\n"), 0);
+    }
+
+    // Right-align line numbers to the widest one in the file so the gutter
+    // lines up however long the file is.
+    let width = lines.len().to_string().len();
+    let mut out = String::new();
+    for (n, block) in blocks.iter().enumerate() {
+        let matches = &block.matches;
+        if n > 0 {
+            out.push_str("--\n");
+        }
+        let numbers: Vec<String> = matches.iter().map(|(i, _)| (i + 1).to_string()).collect();
+        if numbers.len() == 1 {
+            out.push_str(&format!("Match at line {}:\n", numbers[0]));
+        } else {
+            out.push_str(&format!("Matches at lines {}:\n", numbers.join(", ")));
+        }
+
+        out.push_str("```\n");
+        for (i, line) in lines.iter().enumerate().take(block.end).skip(block.start) {
+            let number = if line_numbers {
+                format!("{:>width$}: ", i + 1, width = width)
+            } else {
+                String::new()
+            };
+            if matches.iter().any(|(m, _)| *m == i) {
+                let prefix = format!("{}> ", number);
+                out.push_str(&format!("{}{}\n", prefix, line));
+                out.push_str(&format!(
+                    "{}{}\n",
+                    " ".repeat(prefix.len()),
+                    match_underline(line, regex)
+                ));
+            } else {
+                out.push_str(&format!("{}  {}\n", number, line));
+            }
+        }
+        out.push_str("```\n");
+
+        out.push_str("Captured:\n");
+        for (i, captures) in matches {
+            for (j, capture) in captures.iter().skip(1).enumerate() {
+                if let Some(c) = capture {
+                    if matches.len() == 1 {
+                        out.push_str(&format!("  Group {}: {}\n", j + 1, c.as_str()));
+                    } else {
+                        out.push_str(&format!(
+                            "  Line {} group {}: {}\n",
+                            i + 1,
+                            j + 1,
+                            c.as_str()
+                        ));
+                    }
+                }
+            }
+        }
+        out.push('\n');
+    }
+    let count = blocks.iter().map(|block| block.matches.len()).sum();
+    (out, count)
+}
+
+/// Compares the tree under `--path` with the one under `--diff-path`, as if
+/// they were the old and new side of a git diff. Files are paired up by
+/// their path relative to each root.
+fn print_path_diff(
+    args: &Args,
+    filters: &FileFilters,
+    other: &Path,
+    formatter: &mut OutputFormatter<impl Write>,
+) -> Result<DiffStat, Box<dyn std::error::Error>> {
+    let root = &args.path[0];
+    let old_files = walk_relative(args, root)?;
+    let new_files = walk_relative(args, other)?;
+
+    let out = formatter.writer();
+    writeln!(
+        out,
+        "### Diff from {} to {}",
+        root.display(),
+        other.display()
+    )?;
+
+    let mut stat = DiffStat::default();
+    for relative in old_files.union(&new_files) {
+        if !passes_path_filters(relative, filters) {
+            continue;
+        }
+        let old_path = old_files.contains(relative).then(|| root.join(relative));
+        let new_path = new_files.contains(relative).then(|| other.join(relative));
+
+        // The same filters as a plain walk, applied to both sides.
+        let too_large = [&old_path, &new_path].into_iter().flatten().any(|path| {
+            let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            size_exceeds_limit(path, size, filters)
+        });
+        if too_large {
+            continue;
+        }
+
+        let is_binary = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .is_some_and(|path| is_likely_binary(path, &filters.binary_extensions))
+        };
+        if is_binary(&old_path) || is_binary(&new_path) {
+            let read = |path: &Option<PathBuf>| path.as_ref().map(fs::read).transpose();
+            match (read(&old_path), read(&new_path)) {
+                (Ok(old), Ok(new)) if old != new => {
+                    writeln!(out, "Binary file changed: {}", relative.display())?;
+                    writeln!(out)?;
+                    stat.record(0, 0);
+                }
+                (Ok(_), Ok(_)) => {}
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!(args.quiet, "Error reading {}: {}", relative.display(), e);
+                }
+            }
+            continue;
+        }
+
+        let read = |path: &Option<PathBuf>| -> Result<String, Box<dyn std::error::Error>> {
+            match path {
+                Some(path) => read_text_file(path)?
+                    .ok_or_else(|| format!("{} is not valid text", path.display()).into()),
+                None => Ok(String::new()),
+            }
+        };
+        let (old, new) = match (read(&old_path), read(&new_path)) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!(args.quiet, "Error reading {}: {}", relative.display(), e);
+                continue;
+            }
+        };
+        if old == new {
+            continue;
+        }
+        if looks_minified(&old, args.max_line_length) || looks_minified(&new, args.max_line_length)
+        {
+            verbose!(args, 2, "Skipped (minified): {}", relative.display());
+            continue;
+        }
+
+        let label = |side: &str, path: &Option<PathBuf>| match path {
+            Some(_) => format!("{}/{}", side, relative.display()),
+            None => "/dev/null".to_string(),
+        };
+        let diff = unified_diff(
+            &old,
+            &new,
+            &label("a", &old_path),
+            &label("b", &new_path),
+            args.context_lines,
+            filters.pattern.as_ref(),
+        );
+        if diff.is_empty() {
+            continue;
+        }
+        writeln!(out, "```diff")?;
+        write!(out, "{}", diff)?;
+        writeln!(out, "```")?;
+        writeln!(out)?;
+        let (insertions, deletions) = count_line_changes(&old, &new);
+        stat.record(insertions, deletions);
+    }
+
+    writeln!(out, "{}", stat)?;
+    Ok(stat)
+}
+
+/// Walks `root` with the usual ignore rules and returns every file's path
+/// relative to it.
+fn walk_relative(
+    args: &Args,
+    root: &Path,
+) -> Result<BTreeSet<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = BTreeSet::new();
+    for result in walk_builder(args, root)?.build() {
+        match result {
+            Ok(entry) => {
+                if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                    let path = entry.path();
+                    files.insert(path.strip_prefix(root).unwrap_or(path).to_path_buf());
+                }
+            }
+            Err(e) => warn!(args.quiet, "Error: {}", e),
+        }
+    }
+    Ok(files)
+}
+
+fn print_git_diff(
+    args: &Args,
+    filters: &FileFilters,
+    formatter: &mut OutputFormatter<impl Write>,
+) -> Result<DiffStat, Box<dyn std::error::Error>> {
+    let mut buf1 = Vec::new();
+    let mut buf2 = Vec::new();
+    let repo = open_repo(&args.path[0])?;
+
+    let (from_rev, to_rev) = match (&args.commit, &args.since) {
+        (Some(commit), _) => (format!("{}^", commit), commit.clone()),
+        (None, Some(since)) => (since.clone(), "HEAD".to_string()),
+        (None, None) => (
+            args.git_from.clone().unwrap_or_else(|| "HEAD".to_string()),
+            args.git_to.clone().unwrap_or_else(|| "HEAD".to_string()),
+        ),
+    };
+
+    if from_rev == WORKTREE {
+        return Err(format!("{} can only be used with --git-to", WORKTREE).into());
+    }
+
+    // The parent of a root commit resolves to None, so its changes are
+    // diffed against the empty tree and every file shows up as an addition.
+    let from_obj = find_revision_or_empty(&repo, &from_rev)?;
+    let to_obj = if to_rev == WORKTREE {
+        None
+    } else {
+        Some(find_revision(&repo, &to_rev)?)
+    };
+
+    // Record the resolved commits so the snapshot can be reproduced once
+    // branch names have moved on.
+    let from_label = match from_obj {
+        Some(ref obj) => format!("{} ({})", from_rev, short_oid(obj.id, args.abbrev)),
+        None => format!("{} (empty tree)", from_rev),
+    };
+    let to_label = match to_obj {
+        Some(ref obj) => format!("{} ({})", to_rev, short_oid(obj.id, args.abbrev)),
+        None => to_rev.clone(),
+    };
+    match from_obj {
+        Some(ref obj) => verbose!(args, 1, "Resolved {} to {}", from_rev, obj.id),
+        None => verbose!(args, 1, "Resolved {} to the empty tree", from_rev),
+    }
+    if let Some(ref obj) = to_obj {
+        verbose!(args, 1, "Resolved {} to {}", to_rev, obj.id);
+    }
+
+    let out = formatter.writer();
+    writeln!(out, "### Git diff from {} to {}", from_label, to_label)?;
+
+    let mut worktree = WorktreeBlobs::new();
+    let mut changes = match to_obj {
+        None => {
+            let tree_id = match from_obj {
+                Some(from_obj) => from_obj.peel_to_tree()?.id,
+                None => gix::ObjectId::empty_tree(repo.object_hash()),
+            };
+            diff_tree_to_worktree(&repo, tree_id, &mut worktree)?
+        }
+        Some(to_obj) => {
+            let from_tree = match from_obj {
+                Some(from_obj) => find_tree(&repo, from_obj, &mut buf1)?,
+                None => empty_tree(),
+            };
+            let to_tree = find_tree(&repo, to_obj, &mut buf2)?;
+            diff_trees(&repo, from_tree, to_tree)?
+        }
+    };
+
+    if args.include_untracked {
+        // Filter before reading so large or excluded untracked files (build
+        // output, say) are never loaded.
+        changes.extend(untracked_additions(&repo, &mut worktree, |path, size| {
+            passes_path_filters(path, filters) && !size_exceeds_limit(path, size, filters)
+        })?);
+    }
+
+    // Directory entries are implied by the files inside them.
+    let changes: Vec<Change> = changes
+        .into_iter()
+        .filter(|change| match change {
+            Change::Addition { entry_mode, .. }
+            | Change::Deletion { entry_mode, .. }
+            | Change::Modification { entry_mode, .. } => !entry_mode.is_tree(),
+        })
+        .collect();
+
+    let (changes, renames) = detect_renames(
+        &repo,
+        &worktree,
+        changes,
+        f32::from(args.rename_threshold) / 100.0,
+    )?;
+
+    let wants = |change_type| args.change_types.contains(&change_type);
+    let renames: Vec<Rename> = renames
+        .into_iter()
+        .filter(|_| wants(ChangeType::Rename))
+        .collect();
+    let changes: Vec<Change> = changes
+        .into_iter()
+        .filter(|change| match change {
+            Change::Addition { .. } => wants(ChangeType::Add),
+            Change::Deletion { .. } => wants(ChangeType::Delete),
+            Change::Modification { .. } => wants(ChangeType::Modify),
+        })
+        .collect();
+
+    let mut stat = DiffStat::default();
+    for rename in renames {
+        let from = GitPath::from(&rename.from);
+        let to = GitPath::from(&rename.to);
+        if !passes_path_filters(from.as_ref(), filters)
+            && !passes_path_filters(to.as_ref(), filters)
+        {
+            continue;
+        }
+
+        writeln!(
+            out,
+            "renamed: {} → {}",
+            from.as_ref().display(),
+            to.as_ref().display()
+        )?;
+        writeln!(out)?;
+        if rename.previous_oid == rename.oid {
+            stat.record(0, 0);
+            continue;
+        }
+
+        match process_modification(
+            out,
+            &repo,
+            &worktree,
+            to,
+            filters,
+            rename.previous_oid,
+            rename.oid,
+            args,
+        ) {
+            Ok(Some((insertions, deletions))) => stat.record(insertions, deletions),
+            Ok(None) => {}
+            Err(e) => warn!(
+                args.quiet,
+                "Error processing rename for {:?}: {}", rename.to, e
+            ),
+        }
+    }
+
+    for change in changes {
+        match change {
+            Change::Addition { oid, path, .. } => {
+                match process_change(
+                    out,
+                    &repo,
+                    &worktree,
+                    GitPath::from(&path),
+                    filters,
+                    args,
+                    oid,
+                    true,
+                ) {
+                    Ok(Some(lines)) => stat.record(lines, 0),
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        args.quiet,
+                        "Error processing addition for {:?}: {}", path, e
+                    ),
+                }
+            }
+            Change::Deletion { oid, path, .. } => {
+                match process_change(
+                    out,
+                    &repo,
+                    &worktree,
+                    GitPath::from(&path),
+                    filters,
+                    args,
+                    oid,
+                    false,
+                ) {
+                    Ok(Some(lines)) => stat.record(0, lines),
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        args.quiet,
+                        "Error processing deletion for {:?}: {}", path, e
+                    ),
+                }
+            }
+            Change::Modification {
+                oid,
+                path,
+                previous_oid,
+                ..
+            } => {
+                match process_modification(
+                    out,
+                    &repo,
+                    &worktree,
+                    GitPath::from(&path),
+                    filters,
+                    previous_oid,
+                    oid,
+                    args,
+                ) {
+                    Ok(Some((insertions, deletions))) => stat.record(insertions, deletions),
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        args.quiet,
+                        "Error processing modification for {:?}: {}", path, e
+                    ),
+                }
+            }
+        }
+    }
+
+    writeln!(out, "{}", stat)?;
+
+    Ok(stat)
+}
+
+/// Applies the extension, include and exclude filters to a repo-relative path.
+fn passes_path_filters(path: &Path, filters: &FileFilters) -> bool {
+    if let Some(ref exts) = filters.extensions {
+        if !file_extension_matches(path, exts) {
+            return false;
+        }
+    }
+    if let Some(ref globs) = filters.includes {
+        if !globs.is_match(path) {
+            return false;
+        }
+    }
+    if let Some(ref regexes) = filters.excludes {
+        if regexes
+            .iter()
+            .any(|re| re.is_match(path.to_str().unwrap_or("")))
+        {
+            return false;
+        }
+    }
+    true
+}
+
+fn exceeds_max_file_size(
+    repo: &Repository,
+    worktree: &WorktreeBlobs,
+    path: &Path,
+    oid: gix::ObjectId,
+    filters: &FileFilters,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if filters.max_file_size.is_none() {
+        return Ok(false);
+    }
+    let size = match worktree.get(&oid) {
+        Some(data) => data.len() as u64,
+        None => repo.find_header(oid)?.size(),
+    };
+    Ok(size_exceeds_limit(path, size, filters))
+}
+
+/// Checks `size` against `--max-file-size`, warning about files over it.
+fn size_exceeds_limit(path: &Path, size: u64, filters: &FileFilters) -> bool {
+    match filters.max_file_size {
+        Some(max_size) if size > max_size => {
+            warn!(
+                filters.quiet,
+                "Skipping large file ({} bytes): {}",
+                size,
+                path.display()
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// `oid` as printed in diff headers: its first `abbrev` hex digits, or all of
+/// them for `--abbrev 0`.
+fn short_oid(oid: gix::ObjectId, abbrev: usize) -> String {
+    if abbrev == 0 {
+        oid.to_string()
+    } else {
+        oid.to_hex_with_len(abbrev.min(oid.kind().len_in_hex()))
+            .to_string()
+    }
+}
+
+/// Prints an added or deleted file as a unified diff against `/dev/null`.
+#[allow(clippy::too_many_arguments)]
+fn process_change(
+    out: &mut dyn Write,
+    repo: &Repository,
+    worktree: &WorktreeBlobs,
+    path: impl AsRef<Path>,
+    filters: &FileFilters,
+    args: &Args,
+    oid: gix::ObjectId,
+    added: bool,
+) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if !passes_path_filters(path, filters) {
+        return Ok(None);
+    }
+    if exceeds_max_file_size(repo, worktree, path, oid, filters)? {
+        return Ok(None);
+    }
+    let data = blob_data(repo, worktree, oid)?;
+    if is_binary_blob(path, &data, &filters.binary_extensions) {
+        writeln!(out, "Binary file changed: {}", path.display())?;
+        writeln!(out)?;
+        return Ok(Some(0));
+    }
+
+    // Decoded like modified files, so UTF-16 and Latin-1 text prints too.
+    let text = blob_text(&data);
+    let lines = text.lines().count();
+    let label = format!("{}/{}", if added { "b" } else { "a" }, path.display());
+    let (old, new, old_label, new_label) = if added {
+        ("", text.as_str(), "/dev/null", label.as_str())
+    } else {
+        (text.as_str(), "", label.as_str(), "/dev/null")
+    };
+    let diff = unified_diff(
+        old,
+        new,
+        old_label,
+        new_label,
+        args.context_lines,
+        filters.pattern.as_ref(),
+    );
+    if diff.is_empty() {
+        return Ok(Some(lines));
+    }
+
+    writeln!(out, "OID: {}", short_oid(oid, args.abbrev))?;
+    writeln!(out, "```diff")?;
+    write!(out, "{}", diff)?;
+    writeln!(out, "```")?;
+    writeln!(out)?;
+
+    Ok(Some(lines))
+}
+
+/// Prints a modified file as a unified diff between its two blobs.
+#[allow(clippy::too_many_arguments)]
+fn process_modification(
+    out: &mut dyn Write,
+    repo: &Repository,
+    worktree: &WorktreeBlobs,
+    path: impl AsRef<Path>,
+    filters: &FileFilters,
+    previous_oid: gix::ObjectId,
+    oid: gix::ObjectId,
+    args: &Args,
+) -> Result<Option<(usize, usize)>, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if !passes_path_filters(path, filters)
+        || exceeds_max_file_size(repo, worktree, path, oid, filters)?
+    {
+        return Ok(None);
+    }
+    let previous_data = blob_data(repo, worktree, previous_oid)?;
+    let data = blob_data(repo, worktree, oid)?;
+    if is_binary_blob(path, &previous_data, &filters.binary_extensions)
+        || is_binary_blob(path, &data, &filters.binary_extensions)
+    {
+        writeln!(out, "Binary file changed: {}", path.display())?;
+        writeln!(out)?;
+        return Ok(Some((0, 0)));
+    }
+
+    let old = blob_text(&previous_data);
+    let new = blob_text(&data);
+    let line_changes = count_line_changes(&old, &new);
+    let diff = unified_diff(
+        &old,
+        &new,
+        &format!("a/{}", path.display()),
+        &format!("b/{}", path.display()),
+        args.context_lines,
+        filters.pattern.as_ref(),
+    );
+    if diff.is_empty() {
+        return Ok(Some(line_changes));
+    }
+
+    writeln!(out, "OID: {}", short_oid(oid, args.abbrev))?;
+    writeln!(
+        out,
+        "Previous OID: {}",
+        short_oid(previous_oid, args.abbrev)
+    )?;
+    writeln!(out, "```diff")?;
+    write!(out, "{}", diff)?;
+    writeln!(out, "```")?;
+    writeln!(out)?;
+
+    Ok(Some(line_changes))
+}
+
+fn blob_text(data: &[u8]) -> String {
+    decode_text(data).unwrap_or_else(|| String::from_utf8_lossy(data).into_owned())
+}
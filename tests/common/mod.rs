@@ -0,0 +1,69 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+pub fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "Sample Author")
+        .env("GIT_AUTHOR_EMAIL", "author@example.com")
+        .env("GIT_COMMITTER_NAME", "Sample Author")
+        .env("GIT_COMMITTER_EMAIL", "author@example.com")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", dir)
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+pub fn write_file(root: &Path, relative: &str, contents: impl AsRef<[u8]>) {
+    let path = root.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, contents).unwrap();
+}
+
+pub fn commit_all(dir: &Path, message: &str) {
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "-m", message]);
+}
+
+/// A repository with two commits: the first adds `README.md` and
+/// `src/main.rs`, the second modifies `src/main.rs` and adds `src/lib.rs`.
+pub fn sample_repo() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    git(root, &["init", "-q", "-b", "main"]);
+
+    write_file(root, "README.md", "# Sample\n");
+    write_file(root, "src/main.rs", "fn main() {\n    println!(\"hello\");\n}\n");
+    commit_all(root, "initial commit");
+
+    write_file(root, "src/main.rs", "fn main() {\n    println!(\"hello, world\");\n}\n");
+    write_file(root, "src/lib.rs", "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n");
+    commit_all(root, "second commit");
+
+    dir
+}
+
+pub fn run_repo_walker(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_repo_walker"))
+        .args(args)
+        .output()
+        .expect("failed to run repo_walker")
+}
+
+/// Like [`run_repo_walker`], but runs from `dir` so relative paths resolve
+/// against it.
+pub fn run_repo_walker_in(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_repo_walker"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run repo_walker")
+}
@@ -1,17 +1,25 @@
-use repo_walker::{open_repo, find_revision, find_tree, diff_trees};
-use std::path::PathBuf;
+mod common;
+
+use common::{commit_all, git, run_repo_walker, run_repo_walker_in, sample_repo, write_file};
+use repo_walker::{open_repo, find_revision, find_tree, diff_trees, detect_renames, file_changes, is_binary_bytes, is_likely_binary, read_text_file, BinaryExtensions, FileChange, resolve_blob_at, WorktreeBlobs};
+use gix::diff::tree::recorder::Change;
+use repo_walker::{OutputFormat, OutputFormatter};
+use std::path::Path;
+use tempfile::TempDir;
 
 #[test]
 fn test_open_repo() {
-    let repo_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_data/sample_repo");
-    let result = open_repo(&repo_path);
+    let sample = sample_repo();
+    let repo_path = sample.path();
+    let result = open_repo(repo_path);
     assert!(result.is_ok());
 }
 
 #[test]
 fn test_find_revision() {
-    let repo_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_data/sample_repo");
-    let repo = open_repo(&repo_path).unwrap();
+    let sample = sample_repo();
+    let repo_path = sample.path();
+    let repo = open_repo(repo_path).unwrap();
     
     let result = find_revision(&repo, "HEAD");
     assert!(result.is_ok());
@@ -19,8 +27,9 @@ fn test_find_revision() {
 
 #[test]
 fn test_diff_trees() {
-    let repo_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_data/sample_repo");
-    let repo = open_repo(&repo_path).unwrap();
+    let sample = sample_repo();
+    let repo_path = sample.path();
+    let repo = open_repo(repo_path).unwrap();
     
     let mut buf1 = Vec::new();
     let mut buf2 = Vec::new();
@@ -33,4 +42,1909 @@ fn test_diff_trees() {
     
     let result = diff_trees(&repo, tree1, tree2);
     assert!(result.is_ok());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_json_output() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "fn a() {}\n");
+    write_file(dir.path(), "src/b.rs", "fn b() {}\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--format", "json"]);
+    assert!(output.status.success());
+
+    let snapshot: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(snapshot["files"].as_array().unwrap().len(), 2);
+    assert_eq!(snapshot["total_files"], 2);
+}
+
+#[test]
+fn test_markdown_output() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--format", "markdown"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("## "));
+    assert!(stdout.contains("```rust\nfn main() {}\n```"));
+
+    write_file(dir.path(), "stubs.pyi", "def f() -> int: ...\n");
+    write_file(dir.path(), "setup.zsh", "echo hi\n");
+    write_file(dir.path(), "view.tsx", "export {};\n");
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--format", "markdown"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("```python\ndef f() -> int: ...\n```"));
+    assert!(stdout.contains("```bash\necho hi\n```"));
+    assert!(stdout.contains("```tsx\nexport {};\n```"));
+}
+
+#[test]
+fn test_xml_output_is_well_formed() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "fn a() -> Vec<u8> { vec![] } // a & b\n");
+    write_file(dir.path(), "b.rs", "fn b() {}\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--format", "xml"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let doc = roxmltree::Document::parse(&stdout).unwrap();
+    let root = doc.root_element();
+    assert_eq!(root.tag_name().name(), "documents");
+
+    let documents: Vec<_> = root.children().filter(|n| n.has_tag_name("document")).collect();
+    assert_eq!(documents.len(), 2);
+    assert!(stdout.contains("Vec&lt;u8&gt;"));
+}
+
+#[test]
+fn test_output_to_file() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+    let out_dir = TempDir::new().unwrap();
+    let out_path = out_dir.path().join("out.txt");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--output",
+        out_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let written = std::fs::read_to_string(&out_path).unwrap();
+    assert!(written.contains("### File:"));
+    assert!(written.contains("fn main() {}"));
+}
+
+#[test]
+fn test_is_likely_binary_sniffs_content() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main", b"\x7fELF\x02\x01\x01\x00\x00\x00\x00");
+    write_file(dir.path(), "notes", "plain text\twith tabs\r\n");
+    write_file(dir.path(), "image.png", "not really a png");
+
+    let defaults = BinaryExtensions::default();
+    assert!(is_likely_binary(&dir.path().join("main"), &defaults));
+    assert!(!is_likely_binary(&dir.path().join("notes"), &defaults));
+    assert!(is_likely_binary(&dir.path().join("image.png"), &defaults));
+}
+
+#[test]
+fn test_read_text_file_decodes_utf16_and_latin1() {
+    let dir = TempDir::new().unwrap();
+
+    let mut utf16 = vec![0xFF, 0xFE];
+    for unit in "fn main() {}\r\n".encode_utf16() {
+        utf16.extend_from_slice(&unit.to_le_bytes());
+    }
+    write_file(dir.path(), "utf16.rs", &utf16);
+    write_file(dir.path(), "latin1.txt", b"caf\xe9\n");
+    write_file(dir.path(), "blob", b"\x00\x01\x02\x03");
+
+    let utf16_path = dir.path().join("utf16.rs");
+    let defaults = BinaryExtensions::default();
+    assert!(!is_likely_binary(&utf16_path, &defaults));
+    assert_eq!(
+        read_text_file(&utf16_path).unwrap().as_deref(),
+        Some("fn main() {}\r\n")
+    );
+    assert_eq!(
+        read_text_file(dir.path().join("latin1.txt")).unwrap().as_deref(),
+        Some("caf\u{e9}\n")
+    );
+    assert_eq!(read_text_file(dir.path().join("blob")).unwrap(), None);
+}
+
+#[test]
+fn test_max_file_size_skips_large_files() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "small.rs", "fn small() {}\n");
+    write_file(dir.path(), "large.rs", "// padding\n".repeat(500));
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--max-file-size",
+        "1k",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("small.rs"));
+    assert!(!stdout.contains("large.rs"));
+    assert_eq!(repo_walker::args::parse_size("2M"), Ok(2 * 1024 * 1024));
+    assert!(repo_walker::args::parse_size("lots").is_err());
+}
+
+#[test]
+fn test_gitignore_toggle_and_ignore_file() {
+    let dir = TempDir::new().unwrap();
+    git(dir.path(), &["init", "-q"]);
+    write_file(dir.path(), ".gitignore", "build.log\n");
+    write_file(dir.path(), "build.log", "compiled\n");
+    write_file(dir.path(), "notes.txt", "scratch\n");
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+    let extra_ignore = dir.path().join("extra.ignore");
+    std::fs::write(&extra_ignore, "notes.txt\n").unwrap();
+
+    let root = dir.path().to_str().unwrap();
+    let default_run = run_repo_walker(&["--path", root, "--extensions", "rs,log,txt"]);
+    let stdout = String::from_utf8(default_run.stdout).unwrap();
+    assert!(stdout.contains("main.rs"));
+    assert!(!stdout.contains("build.log"));
+
+    let unignored = run_repo_walker(&["--path", root, "--extensions", "rs,log,txt", "--no-gitignore"]);
+    let stdout = String::from_utf8(unignored.stdout).unwrap();
+    assert!(stdout.contains("build.log"));
+
+    let with_ignore_file = run_repo_walker(&[
+        "--path",
+        root,
+        "--extensions",
+        "rs,log,txt",
+        "--ignore-file",
+        extra_ignore.to_str().unwrap(),
+    ]);
+    let stdout = String::from_utf8(with_ignore_file.stdout).unwrap();
+    assert!(stdout.contains("main.rs"));
+    assert!(!stdout.contains("notes.txt"));
+}
+
+#[test]
+fn test_include_globs() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/lib.rs", "pub fn lib() {}\n");
+    write_file(dir.path(), "src/nested/mod.rs", "pub fn nested() {}\n");
+    write_file(dir.path(), "build.rs", "fn main() {}\n");
+    write_file(dir.path(), "src/skip_me.rs", "fn skipped() {}\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--include",
+        "src/**/*.rs",
+        "--excludes",
+        "skip_me",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("src/lib.rs"));
+    assert!(stdout.contains("src/nested/mod.rs"));
+    assert!(!stdout.contains("build.rs"));
+    assert!(!stdout.contains("skip_me.rs"));
+}
+
+#[test]
+fn test_multiple_paths() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    write_file(first.path(), "alpha.rs", "fn alpha() {}\n");
+    write_file(second.path(), "beta.rs", "fn beta() {}\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        first.path().to_str().unwrap(),
+        "--path",
+        second.path().to_str().unwrap(),
+        "--format",
+        "json",
+    ]);
+    assert!(output.status.success());
+
+    let snapshot: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(snapshot["roots"].as_array().unwrap().len(), 2);
+    let paths: Vec<&str> = snapshot["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["path"].as_str().unwrap())
+        .collect();
+    assert!(paths.iter().any(|p| p.ends_with("alpha.rs")));
+    assert!(paths.iter().any(|p| p.ends_with("beta.rs")));
+}
+
+#[test]
+fn test_files_from_list() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "listed.rs", "fn listed() {}\n");
+    write_file(dir.path(), "also_listed.md", "# listed\n");
+    write_file(dir.path(), "unlisted.rs", "fn unlisted() {}\n");
+
+    let list = dir.path().join("files.txt");
+    std::fs::write(
+        &list,
+        format!(
+            "{}\n\n{}\n",
+            dir.path().join("listed.rs").display(),
+            dir.path().join("also_listed.md").display()
+        ),
+    )
+    .unwrap();
+
+    let output = run_repo_walker(&["--files-from", list.to_str().unwrap(), "--extensions", "rs"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("listed.rs"));
+    assert!(!stdout.contains("unlisted.rs"));
+    assert!(!stdout.contains("also_listed.md"));
+}
+
+#[test]
+fn test_detect_renames() {
+    let sample = sample_repo();
+    let root = sample.path();
+    git(root, &["mv", "src/lib.rs", "src/math.rs"]);
+    commit_all(root, "rename lib.rs");
+
+    let repo = open_repo(root).unwrap();
+    let mut buf1 = Vec::new();
+    let mut buf2 = Vec::new();
+    let tree1 = find_tree(&repo, find_revision(&repo, "HEAD~1").unwrap(), &mut buf1).unwrap();
+    let tree2 = find_tree(&repo, find_revision(&repo, "HEAD").unwrap(), &mut buf2).unwrap();
+    let changes = diff_trees(&repo, tree1, tree2).unwrap();
+
+    let (remaining, renames) = detect_renames(&repo, &WorktreeBlobs::new(), changes, 0.5).unwrap();
+    assert!(remaining
+        .iter()
+        .all(|change| matches!(change, Change::Modification { entry_mode, .. } if entry_mode.is_tree())));
+    assert_eq!(renames.len(), 1);
+    assert_eq!(renames[0].from, "src/lib.rs");
+    assert_eq!(renames[0].to, "src/math.rs");
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "HEAD",
+    ]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("renamed: src/lib.rs → src/math.rs"));
+    assert!(!stdout.contains("+pub fn add"));
+}
+
+#[test]
+fn test_diff_against_worktree() {
+    let sample = sample_repo();
+    let root = sample.path();
+    write_file(root, "README.md", "# Sample\n\nUncommitted line\n");
+    write_file(root, "src/new.rs", "fn staged() {}\n");
+    git(root, &["add", "src/new.rs"]);
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD",
+        "--git-to",
+        "WORKTREE",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+Uncommitted line"));
+    assert!(stdout.contains("+fn staged() {}"));
+    assert!(!stdout.contains("+pub fn add"));
+
+    // Unstaged edits are diffed in memory, not written to the object database.
+    let output = std::process::Command::new("git")
+        .args(["hash-object", "README.md"])
+        .current_dir(root)
+        .output()
+        .unwrap();
+    let blob_id = String::from_utf8(output.stdout).unwrap().trim().to_string();
+    let status = std::process::Command::new("git")
+        .args(["cat-file", "-e", &blob_id])
+        .current_dir(root)
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+fn test_worktree_diff_skips_clean_submodule() {
+    let sample = sample_repo();
+    let root = sample.path();
+    let sub = root.join("sub");
+    std::fs::create_dir(&sub).unwrap();
+    git(&sub, &["init", "-q", "-b", "main"]);
+    write_file(&sub, "g.rs", "fn g() {}\n");
+    commit_all(&sub, "submodule commit");
+    commit_all(root, "add submodule");
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD",
+        "--git-to",
+        "WORKTREE",
+    ]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("0 files changed"));
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-to",
+        "WORKTREE",
+        "--include-untracked",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("fn g()"));
+    assert!(stdout.contains("0 files changed"));
+}
+
+#[test]
+fn test_git_diff_prints_unified_hunks() {
+    let sample = sample_repo();
+    let root = sample.path();
+    let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+    write_file(root, "data.txt", &original);
+    commit_all(root, "add data");
+    write_file(root, "data.txt", original.replace("line 10\n", "line ten\n"));
+    commit_all(root, "edit data");
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "HEAD",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("--- a/data.txt\n+++ b/data.txt\n@@ -7,7 +7,7 @@\n"));
+    assert!(stdout.contains("-line 10\n+line ten\n"));
+    assert!(stdout.contains(" line 13\n"));
+    assert!(!stdout.contains("line 1\n"));
+    assert!(!stdout.contains("line 14"));
+}
+
+#[test]
+fn test_git_diff_reports_diffstat() {
+    let sample = sample_repo();
+
+    let output = run_repo_walker(&[
+        "--path",
+        sample.path().to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "HEAD",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2 files changed, 4 insertions(+), 1 deletion(-)"));
+}
+
+#[test]
+fn test_change_types_filter() {
+    let sample = sample_repo();
+
+    let output = run_repo_walker(&[
+        "--path",
+        sample.path().to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "HEAD",
+        "--change-types",
+        "add",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+pub fn add(a: i32, b: i32) -> i32 {"));
+    assert!(!stdout.contains("hello, world"));
+    assert!(stdout.contains("1 file changed"));
+}
+
+#[test]
+fn test_find_tree_missing_object_is_an_error() {
+    let sample = sample_repo();
+    let root = sample.path();
+
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "HEAD^{tree}"])
+        .current_dir(root)
+        .output()
+        .unwrap();
+    let tree_id = String::from_utf8(output.stdout).unwrap().trim().to_string();
+    let object_path = root
+        .join(".git/objects")
+        .join(&tree_id[..2])
+        .join(&tree_id[2..]);
+    std::fs::remove_file(object_path).unwrap();
+
+    let repo = open_repo(root).unwrap();
+    let mut buf = Vec::new();
+    let commit = find_revision(&repo, "HEAD").unwrap();
+    assert!(find_tree(&repo, commit, &mut buf).is_err());
+}
+
+#[test]
+fn test_invalid_regex_is_reported_without_panicking() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+    let path = dir.path().to_str().unwrap();
+
+    for (flag, value) in [("--pattern", "("), ("--excludes", "[")] {
+        let output = run_repo_walker(&["-p", path, flag, value]);
+        assert!(!output.status.success());
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"));
+        assert!(stderr.contains(&format!("invalid regex for {}", flag)));
+    }
+}
+
+#[test]
+fn test_git_diff_summarizes_binary_blobs() {
+    let sample = sample_repo();
+    let root = sample.path();
+    write_file(root, "assets/logo.dat", [0x89, b'P', b'N', b'G', 0x00, 0xff, 0x10]);
+    commit_all(root, "add binary");
+    write_file(root, "assets/logo.dat", [0x89, b'P', b'N', b'G', 0x00, 0xfe, 0x11]);
+    commit_all(root, "change binary");
+
+    for from in ["HEAD~2", "HEAD~1"] {
+        let output = run_repo_walker(&[
+            "--path",
+            root.to_str().unwrap(),
+            "--git-from",
+            from,
+            "--git-to",
+            "HEAD",
+        ]);
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Binary file changed: assets/logo.dat"));
+        assert!(!stdout.contains("PNG"));
+        assert!(stdout.contains("1 file changed"));
+        assert!(String::from_utf8(output.stderr).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_pattern_context_merges_overlapping_windows() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(
+        dir.path(),
+        "notes.txt",
+        "one\ntwo\nTODO first\nfour\nTODO second\nsix\nseven\neight\nnine\nten\nTODO third\n",
+    );
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--pattern",
+        "TODO",
+        "-c",
+        "1",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Matches at lines 3, 5:"));
+    assert_eq!(stdout.matches("4:   four").count(), 1);
+    assert_eq!(stdout.matches("--\n").count(), 1);
+    assert!(stdout.contains("Match at line 11:"));
+}
+
+#[test]
+fn test_pattern_matches_go_through_output_format() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {\n    // TODO(ann): tidy\n}\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--pattern",
+        r"TODO\((\w+)\)",
+        "--format",
+        "json",
+    ]);
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["total_files"], 1);
+    let contents = json["files"][0]["contents"].as_str().unwrap();
+    assert!(contents.contains("2: >     // TODO(ann): tidy"));
+    assert!(contents.contains("Group 1: ann"));
+}
+
+#[test]
+fn test_stats_only_omits_file_bodies() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {\n    run();\n}\n");
+    write_file(dir.path(), "README.md", "# Title\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--stats-only"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("main.rs (3 lines, 25 B)"));
+    assert!(stdout.contains("Total: 2 files, 4 lines, 33 B"));
+    assert!(!stdout.contains("run();"));
+    assert!(!stdout.contains("```"));
+}
+
+#[test]
+fn test_file_header_reports_lines_and_size() {
+    let dir = TempDir::new().unwrap();
+    let contents = "x = 1\n".repeat(700);
+    write_file(dir.path(), "big.py", &contents);
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!("big.py ({} lines, 4.1 KB)", contents.lines().count());
+    assert!(stdout.contains(&expected));
+
+    write_file(dir.path(), "one.txt", "one\n");
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--toc"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2. one.txt (1 line, 4 B)"));
+    assert!(stdout.contains("### File: one.txt (1 line, 4 B)"));
+}
+
+#[test]
+fn test_sort_orders_files() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "zeta.txt", "z\n");
+    write_file(dir.path(), "alpha.txt", "a\n");
+    write_file(dir.path(), "mid/beta.txt", "b\n".repeat(50));
+    let root = dir.path().to_str().unwrap();
+
+    let position = |stdout: &str, name: &str| stdout.find(name).unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--sort", "path"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(position(&stdout, "alpha.txt") < position(&stdout, "beta.txt"));
+    assert!(position(&stdout, "beta.txt") < position(&stdout, "zeta.txt"));
+
+    let output = run_repo_walker(&["--path", root, "--sort", "size"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(position(&stdout, "beta.txt") < position(&stdout, "alpha.txt"));
+}
+
+#[test]
+fn test_exclude_dir_prunes_directories() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/main.rs", "fn main() {}\n");
+    write_file(dir.path(), "target/debug/build.log", "compiled\n");
+    write_file(dir.path(), "crates/core/target/out.txt", "nested\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--no-gitignore",
+        "--exclude-dir",
+        "target",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("main.rs"));
+    assert!(!stdout.contains("target"));
+    assert!(!stdout.contains("compiled"));
+}
+
+#[test]
+fn test_skipped_files_are_reported_by_reason() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+    write_file(dir.path(), "logo.png", [0x89, b'P', b'N', b'G', 0x00]);
+    write_file(dir.path(), "generated.rs", "// generated\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--excludes",
+        "generated",
+    ]);
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Skipped files: binary: 1, excluded: 1"));
+}
+
+#[test]
+fn test_dry_run_lists_paths_only() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/main.rs", "fn main() {\n    run();\n}\n");
+    write_file(dir.path(), "notes.txt", "remember\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--extensions",
+        "rs",
+        "--dry-run",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line == "src/main.rs"));
+    assert!(!stdout.contains("notes.txt"));
+    assert!(!stdout.contains("run();"));
+    assert!(stdout.contains("Total: 1 file, 3 lines"));
+}
+
+#[test]
+fn test_toc_lists_files_before_contents() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "fn a() {}\n");
+    write_file(dir.path(), "b.rs", "fn b() {}\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--toc"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let (toc, body) = stdout.split_once("### File:").unwrap();
+    assert!(toc.starts_with("### Table of Contents"));
+
+    let listed: Vec<&str> = toc
+        .lines()
+        .filter_map(|line| line.split_once(". ").map(|(_, rest)| rest))
+        .map(|rest| rest.split(" (").next().unwrap())
+        .collect();
+    let sections: Vec<&str> = body
+        .split("### File: ")
+        .map(|section| section.trim_start().split(" (").next().unwrap())
+        .collect();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed, sections);
+}
+
+#[test]
+fn test_pattern_match_is_underlined() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "lib.rs", "let total = compute(x);\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--pattern",
+        "compute",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip_while(|line| !line.starts_with("1: > "));
+    let matched = lines.next().unwrap();
+    let underline = lines.next().unwrap();
+    let column = matched.find("compute").unwrap();
+    assert_eq!(underline.find('^'), Some(column));
+    assert_eq!(underline.trim(), "^^^^^^^");
+}
+
+#[test]
+fn test_ignore_case_pattern() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "lib.rs", "// todo: handle errors\n");
+    let path = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", path, "--pattern", "TODO"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("No matches found in this file."));
+
+    let output = run_repo_walker(&["--path", path, "--pattern", "TODO", "--ignore-case"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("1: > // todo: handle errors"));
+}
+
+#[test]
+fn test_pattern_mode_reports_match_totals() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "// TODO one\nfn a() {}\n// TODO two\n");
+    write_file(dir.path(), "b.rs", "// TODO three\n");
+    write_file(dir.path(), "c.rs", "fn c() {}\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--pattern", "TODO"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("Pattern matches: 3 across 2 files\n"));
+}
+
+#[test]
+fn test_invert_match_keeps_files_without_matches() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "licensed.rs", "// SPDX-License-Identifier: MIT\nfn a() {}\n");
+    write_file(dir.path(), "unlicensed.rs", "fn b() {}\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--pattern",
+        "SPDX-License-Identifier",
+        "--invert-match",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("unlicensed.rs"));
+    assert!(stdout.contains("fn b() {}"));
+    assert!(!stdout.contains("/licensed.rs"));
+    assert!(!stdout.contains("fn a() {}"));
+}
+
+#[test]
+fn test_files_with_matches_prints_only_paths() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "// TODO one\n");
+    write_file(dir.path(), "b.rs", "fn b() {}\n");
+    write_file(dir.path(), "c.rs", "// TODO two\n// TODO three\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--pattern",
+        "TODO",
+        "--files-with-matches",
+        "--sort",
+        "path",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "a.rs\nc.rs\n");
+}
+
+#[test]
+fn test_commit_diffs_against_its_parent() {
+    let sample = sample_repo();
+    let root = sample.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("### Git diff from HEAD^ ("));
+    assert!(stdout.contains("+    println!(\"hello, world\");"));
+    assert!(stdout.contains("2 files changed"));
+
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD~1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+# Sample"));
+    assert!(stdout.contains("+    println!(\"hello\");"));
+    assert!(stdout.contains("2 files changed, 4 insertions(+), 0 deletions(-)"));
+}
+
+#[test]
+fn test_git_from_parent_of_root_commit_uses_empty_tree() {
+    let sample = sample_repo();
+    let root = sample.path();
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD~1^",
+        "--git-to",
+        "HEAD~1",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+# Sample"));
+    assert!(stdout.contains("+fn main() {"));
+    assert!(stdout.contains("2 files changed, 4 insertions(+), 0 deletions(-)"));
+
+    write_file(root, "README.md", "# Sample\nMore.\n");
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD~1~1",
+        "--git-to",
+        "WORKTREE",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+More."));
+    assert!(stdout.contains("+pub fn add(a: i32, b: i32) -> i32 {"));
+}
+
+#[test]
+fn test_git_additions_and_deletions_are_labelled_with_their_path() {
+    let sample = sample_repo();
+    let root = sample.path();
+    std::fs::remove_file(root.join("README.md")).unwrap();
+    commit_all(root, "remove readme");
+
+    let output = run_repo_walker(&[
+        "--path",
+        root.to_str().unwrap(),
+        "--git-from",
+        "HEAD~2",
+        "--git-to",
+        "HEAD",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("--- a/README.md\n+++ /dev/null\n@@ -1 +0,0 @@\n-# Sample\n"));
+    assert!(stdout.contains("--- /dev/null\n+++ b/src/lib.rs\n@@ -0,0 +1,3 @@\n"));
+}
+
+#[test]
+fn test_file_changes_maps_recorder_changes() {
+    let sample = sample_repo();
+    let root = sample.path();
+    git(root, &["mv", "README.md", "README.txt"]);
+    commit_all(root, "rename readme");
+
+    let repo = open_repo(root).unwrap();
+    let mut buf1 = Vec::new();
+    let mut buf2 = Vec::new();
+    let tree1 = find_tree(&repo, find_revision(&repo, "HEAD~2").unwrap(), &mut buf1).unwrap();
+    let tree2 = find_tree(&repo, find_revision(&repo, "HEAD").unwrap(), &mut buf2).unwrap();
+
+    let changes = file_changes(&repo, tree1, tree2, 0.5).unwrap();
+    assert_eq!(changes.len(), 3);
+    assert!(changes.iter().any(|change| matches!(
+        change,
+        FileChange::Renamed { from, to, similarity, .. }
+            if from == "README.md" && to == "README.txt" && *similarity == 1.0
+    )));
+    assert!(changes.iter().any(|change| matches!(
+        change,
+        FileChange::Modified { path, previous_oid, oid } if path == "src/main.rs" && previous_oid != oid
+    )));
+    assert!(changes
+        .iter()
+        .any(|change| matches!(change, FileChange::Added { path, .. } if path == "src/lib.rs")));
+}
+
+#[test]
+fn test_open_repo_from_subdirectory() {
+    let sample = sample_repo();
+    let nested = sample.path().join("src");
+
+    let repo = open_repo(&nested).unwrap();
+    assert!(find_revision(&repo, "HEAD~1").is_ok());
+
+    let output = run_repo_walker(&[
+        "--path",
+        nested.to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "HEAD",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+pub fn add(a: i32, b: i32) -> i32 {"));
+}
+
+#[test]
+fn test_relative_path_to_subdirectory_finds_repo() {
+    let sample = sample_repo();
+
+    let output = run_repo_walker_in(sample.path(), &["--path", "src", "--commit", "HEAD"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+pub fn add(a: i32, b: i32) -> i32 {"));
+}
+
+#[test]
+fn test_run_returns_a_report() {
+    use clap::Parser;
+
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+    write_file(dir.path(), "lib.rs", "pub fn a() {}\npub fn b() {}\n");
+    write_file(dir.path(), "logo.png", [0x89, b'P', b'N', b'G', 0x00]);
+
+    let args = repo_walker::Args::parse_from(["repo_walker", "--path", dir.path().to_str().unwrap()]);
+    let mut formatter = repo_walker::formatter_for(&args, Vec::new());
+    let report = repo_walker::run(&args, &mut formatter).unwrap();
+    formatter.finish().unwrap();
+
+    let rendered = String::from_utf8(formatter.into_sink()).unwrap();
+    assert_eq!(rendered.matches("### File:").count(), 2);
+
+    assert_eq!(report.files.len(), 2);
+    assert_eq!(report.skipped.binary, 1);
+    assert_eq!(report.files.iter().map(|file| file.lines).sum::<usize>(), 3);
+    assert!(report.diff_stat.is_none());
+}
+
+#[test]
+fn test_formatter_renders_into_any_sink() {
+    let mut formatter = OutputFormatter::new(OutputFormat::Markdown, ".").with_sink(Vec::new());
+    formatter
+        .print_file_contents(Path::new("src/lib.rs"), "pub fn a() {}\n")
+        .unwrap();
+    formatter.finish().unwrap();
+
+    assert_eq!(
+        String::from_utf8(formatter.into_sink()).unwrap(),
+        "## src/lib.rs\n\n```rust\npub fn a() {}\n```\n\n"
+    );
+
+    let mut formatter = OutputFormatter::new(OutputFormat::Plain, ".").with_sink(Vec::new());
+    formatter
+        .print_file_contents(Path::new("notes.txt"), "one\ntwo\n")
+        .unwrap();
+    formatter.finish().unwrap();
+
+    assert_eq!(
+        String::from_utf8(formatter.into_sink()).unwrap(),
+        "### File: notes.txt (2 lines, 8 B)\n```\none\ntwo\n\n```\n\n"
+    );
+}
+
+#[test]
+fn test_paths_are_relative_to_the_walk_root() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/main.rs", "fn main() {}\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("### File: src/main.rs ("));
+    assert!(!stdout.contains(root));
+
+    let output = run_repo_walker(&["--path", root, "--absolute-paths"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let absolute = dir.path().join("src/main.rs");
+    assert!(stdout.contains(&format!("### File: {} (", absolute.display())));
+}
+
+#[test]
+fn test_git_diff_in_bare_repository() {
+    let sample = sample_repo();
+    let mirror = TempDir::new().unwrap();
+    let bare = mirror.path().join("sample.git");
+    git(
+        mirror.path(),
+        &["clone", "-q", "--bare", sample.path().to_str().unwrap(), bare.to_str().unwrap()],
+    );
+
+    let output = run_repo_walker(&[
+        "--path",
+        bare.to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "HEAD",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+pub fn add(a: i32, b: i32) -> i32 {"));
+    assert!(stdout.contains("2 files changed"));
+
+    let output = run_repo_walker(&[
+        "--path",
+        bare.to_str().unwrap(),
+        "--git-from",
+        "HEAD",
+        "--git-to",
+        "WORKTREE",
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("WORKTREE cannot be used with a bare repository"));
+}
+
+#[test]
+fn test_progress_only_writes_to_stderr() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "fn a() {}\n");
+    write_file(dir.path(), "b.rs", "fn b() {}\n");
+    let root = dir.path().to_str().unwrap();
+
+    let quiet = run_repo_walker(&["--path", root]);
+    let verbose = run_repo_walker(&["--path", root, "--progress"]);
+    assert!(verbose.status.success());
+    assert_eq!(quiet.stdout, verbose.stdout);
+
+    let stderr = String::from_utf8(verbose.stderr).unwrap();
+    assert!(stderr.contains("Processed 1 files"));
+}
+
+#[test]
+fn test_languages_expand_to_extensions() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/main.rs", "fn main() {}\n");
+    write_file(dir.path(), "README.md", "# Readme\n");
+    write_file(dir.path(), "tool.py", "print('hi')\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--languages",
+        "rust",
+        "--extensions",
+        "py",
+    ]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("src/main.rs"));
+    assert!(stdout.contains("tool.py"));
+    assert!(!stdout.contains("README.md"));
+}
+
+#[test]
+fn test_blame_annotates_headers() {
+    let sample = sample_repo();
+    write_file(sample.path(), "notes.txt", "untracked\n");
+
+    let output = run_repo_walker(&["--path", sample.path().to_str().unwrap(), "--blame"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let header = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with(&format!("### File: {} (", name)))
+            .unwrap_or_else(|| panic!("no header for {} in:\n{}", name, stdout))
+            .to_string()
+    };
+    assert!(header("src/lib.rs").contains("last changed by Sample Author on "));
+    assert!(header("README.md").contains("last changed by Sample Author on "));
+    assert!(!header("notes.txt").contains("last changed"));
+
+    // --files-from finds the repository of each listed file rather than the
+    // one in the current directory.
+    let list = sample.path().join("list.txt");
+    std::fs::write(&list, format!("{}\n", sample.path().join("src/lib.rs").display())).unwrap();
+    let output = run_repo_walker(&["--files-from", list.to_str().unwrap(), "--blame"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("last changed by Sample Author on "), "{}", stdout);
+}
+
+#[test]
+fn test_resolve_blob_at() {
+    let sample = sample_repo();
+    let repo = open_repo(sample.path()).unwrap();
+
+    let id = resolve_blob_at(&repo, "src/lib.rs", "HEAD").unwrap();
+    let expected = repo.rev_parse_single("HEAD:src/lib.rs").unwrap().detach();
+    assert_eq!(id, Some(expected));
+
+    assert_eq!(resolve_blob_at(&repo, "src/missing.rs", "HEAD").unwrap(), None);
+    assert_eq!(resolve_blob_at(&repo, "src/lib.rs", "HEAD~1").unwrap(), None);
+    assert_eq!(resolve_blob_at(&repo, "src", "HEAD").unwrap(), None);
+}
+
+#[test]
+fn test_head_limits_printed_lines() {
+    let dir = TempDir::new().unwrap();
+    let contents: String = (1..=100).map(|i| format!("row {}\n", i)).collect();
+    write_file(dir.path(), "fixture.txt", contents);
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--head", "10"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<&str> = stdout.lines().filter(|l| l.starts_with("row ")).collect();
+    assert_eq!(rows.len(), 10);
+    assert_eq!(rows.last(), Some(&"row 10"));
+    assert!(stdout.contains("… (90 more lines omitted)"));
+    // The note is not part of the printed text.
+    assert!(stdout.contains("### File: fixture.txt (10 lines, 61 B)"));
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--head",
+        "3",
+        "--stats-only",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Total: 1 file, 3 lines, 18 B"));
+}
+
+#[test]
+fn test_binary_extensions() {
+    let defaults = BinaryExtensions::default();
+    for ext in [
+        "svg", "ico", "woff", "woff2", "ttf", "otf", "eot", "class", "o", "a", "wasm", "bin",
+        "jar", "war",
+    ] {
+        let name = format!("asset.{}", ext);
+        assert!(is_likely_binary(Path::new(&name), &defaults), "{} should be binary", name);
+    }
+    assert!(is_likely_binary(Path::new("FONT.TTF"), &defaults));
+}
+
+#[test]
+fn test_text_extensions_override_binary_list() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "logo.svg", "<svg><circle r=\"4\"/></svg>\n");
+    write_file(dir.path(), "fixture.json", "{\"large\": true}\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("<circle"));
+    assert!(stdout.contains("\"large\""));
+
+    let output = run_repo_walker(&[
+        "--path",
+        root,
+        "--text-extensions",
+        "svg",
+        "--binary-extensions",
+        ".json",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("<svg><circle r=\"4\"/></svg>"));
+    assert!(!stdout.contains("\"large\""));
+}
+
+#[test]
+fn test_repowalkerignore_excludes_files() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), ".repowalkerignore", "docs/\n");
+    write_file(dir.path(), "docs/guide.md", "# Guide\n");
+    write_file(dir.path(), "src/lib.rs", "pub fn kept() {}\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--no-gitignore"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("src/lib.rs"));
+    assert!(!stdout.contains("guide.md"));
+}
+
+#[test]
+fn test_quiet_suppresses_skip_warnings() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "garbled.dat", b"\x00\x81\x8d\xff");
+    write_file(dir.path(), "large.txt", "x".repeat(2048));
+    write_file(dir.path(), "ok.txt", "fine\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--max-file-size", "1k"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipping large file"));
+    assert!(stderr.contains("binary: 1"));
+
+    let output = run_repo_walker(&["--path", root, "--max-file-size", "1k", "--quiet"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("fine"));
+}
+
+#[test]
+fn test_git_diff_decodes_added_files() {
+    let sample = sample_repo();
+    write_file(sample.path(), "notes.txt", b"first\ncaf\xe9 au lait\nlast\n");
+    let utf16: Vec<u8> = std::iter::once(0xFEFF_u16)
+        .chain("wide text\n".encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    write_file(sample.path(), "wide.txt", utf16);
+    commit_all(sample.path(), "add encoded notes");
+    let root = sample.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+first\n+café au lait\n+last\n"));
+    assert!(stdout.contains("+wide text\n"));
+    assert!(stdout.contains("2 files changed, 4 insertions(+), 0 deletions(-)"));
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_verbose_reports_filter_diagnostics() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "main.rs", "fn main() {}\n");
+    write_file(dir.path(), "notes.txt", "notes\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--extensions", "rs"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("Skipped ("));
+    assert!(!stderr.contains("passed the filters"));
+
+    let output = run_repo_walker(&["--path", root, "--extensions", "rs", "-vv"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipped (not in --extensions): "));
+    assert!(stderr.contains("notes.txt"));
+    assert!(stderr.contains("1 file(s) passed the filters, 1 skipped"));
+}
+
+#[test]
+fn test_git_diff_header_shows_resolved_ids() {
+    let sample = sample_repo();
+    let repo = open_repo(sample.path()).unwrap();
+    let short = |rev: &str| {
+        repo.rev_parse_single(rev)
+            .unwrap()
+            .detach()
+            .to_hex_with_len(7)
+            .to_string()
+    };
+
+    let output = run_repo_walker(&[
+        "--path",
+        sample.path().to_str().unwrap(),
+        "--git-from",
+        "HEAD~1",
+        "--git-to",
+        "main",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let header = stdout.lines().next().unwrap();
+    assert_eq!(
+        header,
+        format!(
+            "### Git diff from HEAD~1 ({}) to main ({})",
+            short("HEAD~1"),
+            short("HEAD")
+        )
+    );
+}
+
+#[test]
+fn test_ignore_blank_lines_shrinks_totals() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "padded.rs", "fn a() {}\n\n    \n\nfn b() {}\n\t\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--stats-only"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Total: 1 file, 6 lines"), "{}", stdout);
+
+    let output = run_repo_walker(&["--path", root, "--stats-only", "--ignore-blank-lines"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Total: 1 file, 2 lines, 20 B"), "{}", stdout);
+
+    let output = run_repo_walker(&["--path", root, "--ignore-blank-lines"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("fn a() {}\nfn b() {}\n"));
+}
+
+#[test]
+fn test_is_binary_bytes() {
+    assert!(!is_binary_bytes(b""));
+    assert!(!is_binary_bytes(b"fn main() {\n\tprintln!(\"hi\");\r\n}\n"));
+    assert!(!is_binary_bytes("caf\u{e9} \u{2713}\n".as_bytes()));
+    assert!(!is_binary_bytes(b"\xff\xfeh\x00i\x00"));
+    assert!(is_binary_bytes(b"\x7fELF\x02\x01\x01\x00"));
+    assert!(is_binary_bytes(b"\x01\x02\x03\x04abc"));
+
+    // Only the start of large data is sniffed.
+    let mut late_nul = vec![b'a'; 16 * 1024];
+    late_nul.push(0);
+    assert!(!is_binary_bytes(&late_nul));
+}
+
+#[test]
+fn test_since_diffs_to_head() {
+    let sample = sample_repo();
+    write_file(sample.path(), "README.md", "# Sample\n\nThird commit.\n");
+    commit_all(sample.path(), "third commit");
+    let root = sample.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--since", "HEAD~2"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("### Git diff from HEAD~2 ("));
+    assert!(stdout.contains("+Third commit."));
+    assert!(stdout.contains("+    println!(\"hello, world\");"));
+    assert!(stdout.contains("pub fn add"));
+
+    let output = run_repo_walker(&["--path", root, "--since", "HEAD~2", "--git-from", "HEAD~1"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_stats_break_down_by_language() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/lib.rs", "pub fn a() {}\npub fn b() {}\n");
+    write_file(dir.path(), "src/main.rs", "fn main() {}\n");
+    write_file(dir.path(), "cmd/main.go", "package main\n");
+    write_file(dir.path(), "Makefile", "all:\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--stats-only"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "By language: rust: 2 files, 3 lines; go: 1 file, 1 line; other: 1 file, 1 line"
+    ));
+}
+
+#[test]
+fn test_stats_break_down_by_directory() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/lib.rs", "pub fn a() {}\npub fn b() {}\n");
+    write_file(dir.path(), "src/git/diff.rs", "fn diff() {}\n");
+    write_file(dir.path(), "tests/it.rs", "#[test]\nfn it() {}\nfn helper() {}\n");
+    write_file(dir.path(), "README.md", "# Readme\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--stats-only"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "By directory: src: 2 files, 3 lines; tests: 1 file, 3 lines; .: 1 file, 1 line"
+    ));
+
+    let output = run_repo_walker(&["--path", root, "--stats-only", "--group-depth", "2"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "By directory: tests: 1 file, 3 lines; src: 1 file, 2 lines; .: 1 file, 1 line; src/git: 1 file, 1 line"
+    ));
+}
+
+#[test]
+fn test_stats_list_largest_files() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "small.rs", "fn s() {}\n");
+    write_file(dir.path(), "big.rs", "fn b() {}\n".repeat(50));
+    write_file(dir.path(), "medium.rs", "fn m() {}\n".repeat(5));
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--stats-only", "--top", "2"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Largest files: big.rs (50 lines, 500 B); medium.rs (5 lines, 50 B)\n"));
+
+    let output = run_repo_walker(&["--path", root, "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Largest files: big.rs (50 lines, 500 B); medium.rs (5 lines, 50 B); small.rs (1 line, 10 B)\n"));
+
+    let output = run_repo_walker(&["--path", root, "--stats-only", "--top", "0"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Largest files"));
+}
+
+#[test]
+fn test_bom_and_whitespace_only_files_are_skipped() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "bom.rs", b"\xef\xbb\xbffn main() {}\n");
+    write_file(dir.path(), "bom_only.txt", b"\xef\xbb\xbf");
+    write_file(dir.path(), "blank.txt", " \n\t\n");
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("```\nfn main() {}\n"));
+    assert!(!stdout.contains('\u{feff}'));
+    assert!(!stdout.contains("bom_only.txt"));
+    assert!(!stdout.contains("blank.txt"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("empty: 2"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_follow_symlinks() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "shared/util.rs", "pub fn shared_util() {}\n");
+    write_file(dir.path(), "src/main.rs", "fn main() {}\n");
+    std::os::unix::fs::symlink(
+        dir.path().join("shared/util.rs"),
+        dir.path().join("src/linked.rs"),
+    )
+    .unwrap();
+    // A directory that links back to its parent must not hang the walk.
+    std::os::unix::fs::symlink(dir.path().join("src"), dir.path().join("src/loop")).unwrap();
+    let root = dir.path().join("src");
+    let root = root.to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("linked.rs"));
+
+    let output = run_repo_walker(&["--path", root, "--follow-symlinks"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("### File: linked.rs"));
+    assert!(stdout.contains("pub fn shared_util() {}"));
+    assert_eq!(stdout.matches("### File: ").count(), 2);
+}
+
+#[test]
+fn test_max_depth_limits_walk() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "top.rs", "fn top() {}\n");
+    write_file(dir.path(), "a/b/deep.rs", "fn deep() {}\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--max-depth", "1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("top.rs"));
+    assert!(!stdout.contains("deep.rs"));
+
+    let output = run_repo_walker(&["--path", root, "--max-depth", "3"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("deep.rs"));
+
+    let output = run_repo_walker(&["--path", root, "--max-depth", "0"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_no_line_numbers_in_match_blocks() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "lib.rs", "fn a() {}\n// FIXME later\nfn b() {}\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--pattern", "FIXME", "-c", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1:   fn a() {}\n2: > // FIXME later\n"));
+
+    let output = run_repo_walker(&[
+        "--path",
+        root,
+        "--pattern",
+        "FIXME",
+        "-c",
+        "1",
+        "--no-line-numbers",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("```\n  fn a() {}\n> // FIXME later\n     ^^^^^\n  fn b() {}\n```"));
+    assert!(stdout.contains("Match at line 2:"));
+}
+
+#[test]
+fn test_match_gutter_width_follows_file_length() {
+    let dir = TempDir::new().unwrap();
+    let mut contents: String = (1..=12_000).map(|i| format!("line {}\n", i)).collect();
+    contents = contents.replace("line 5\n", "MARK 5\n").replace("line 11999\n", "MARK 11999\n");
+    write_file(dir.path(), "big.txt", contents);
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--pattern",
+        "MARK",
+        "-c",
+        "1",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("    4:   line 4\n    5: > MARK 5\n"));
+    assert!(stdout.contains("11998:   line 11998\n11999: > MARK 11999\n"));
+    assert!(stdout.contains("\n12000:   line 12000\n"));
+}
+
+#[test]
+fn test_minified_files_are_skipped() {
+    let dir = TempDir::new().unwrap();
+    let minified: String = (0..6000).map(|i| format!("var a{}=1;", i % 10)).collect();
+    assert!(minified.len() >= 50_000);
+    write_file(dir.path(), "bundle.min.js", &minified);
+    write_file(dir.path(), "app.js", "const a = 1;\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("app.js"));
+    assert!(!stdout.contains("bundle.min.js"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("minified: 1"));
+
+    let output = run_repo_walker(&["--path", root, "--max-line-length", "0"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("bundle.min.js"));
+}
+
+#[test]
+fn test_abbreviated_oids_in_diff() {
+    let sample = sample_repo();
+    let repo = open_repo(sample.path()).unwrap();
+    let full = repo.rev_parse_single("HEAD:src/lib.rs").unwrap().detach().to_string();
+    let root = sample.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("OID: {}\n", &full[..7])));
+
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD", "--abbrev", "0"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("OID: {}\n", full)));
+
+    let head = repo.rev_parse_single("HEAD").unwrap().detach().to_string();
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD", "--abbrev", "12"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!(" to HEAD ({})\n", &head[..12])));
+}
+
+#[test]
+fn test_collapsible_markdown_sections() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.rs", "fn a() {}\n");
+    write_file(dir.path(), "b.go", "package b\n");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--format", "markdown", "--collapsible"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(
+        "<details>\n<summary>a.rs (1 line, 10 B)</summary>\n\n```rust\nfn a() {}\n```\n\n</details>\n"
+    ));
+    assert!(stdout.contains("<summary>b.go (1 line, 10 B)</summary>"));
+    assert_eq!(stdout.matches("<details>").count(), 2);
+    assert_eq!(stdout.matches("</details>").count(), 2);
+    assert!(!stdout.contains("## a.rs"));
+
+    let output = run_repo_walker(&["--path", root, "--collapsible"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_diff_path_compares_two_directories() {
+    let upstream = TempDir::new().unwrap();
+    let vendored = TempDir::new().unwrap();
+    write_file(upstream.path(), "src/lib.rs", "pub fn a() {}\npub fn b() {}\n");
+    write_file(upstream.path(), "same.txt", "unchanged\n");
+    write_file(upstream.path(), "old.txt", "removed upstream\n");
+    write_file(vendored.path(), "src/lib.rs", "pub fn a() {}\npub fn b2() {}\n");
+    write_file(vendored.path(), "same.txt", "unchanged\n");
+    write_file(vendored.path(), "new.txt", "patched locally\n");
+
+    let output = run_repo_walker(&[
+        "--path",
+        upstream.path().to_str().unwrap(),
+        "--diff-path",
+        vendored.path().to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+    assert!(stdout.contains("-pub fn b() {}\n+pub fn b2() {}\n"));
+    assert!(stdout.contains("--- /dev/null\n+++ b/new.txt\n"));
+    assert!(stdout.contains("--- a/old.txt\n+++ /dev/null\n"));
+    assert!(!stdout.contains("same.txt"));
+    assert!(stdout.contains("3 files changed, 2 insertions(+), 2 deletions(-)"));
+
+    write_file(upstream.path(), "big.txt", "small\n");
+    write_file(vendored.path(), "big.txt", "x\n".repeat(2048));
+    write_file(vendored.path(), "bundle.js", format!("{}\n", "var a=1;".repeat(200)));
+    let output = run_repo_walker(&[
+        "--path",
+        upstream.path().to_str().unwrap(),
+        "--diff-path",
+        vendored.path().to_str().unwrap(),
+        "--max-file-size",
+        "1k",
+        "--max-line-length",
+        "100",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("big.txt"));
+    assert!(!stdout.contains("bundle.js"));
+    assert!(stdout.contains("3 files changed, 2 insertions(+), 2 deletions(-)"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Skipping large file (4096 bytes)"));
+}
+
+#[test]
+fn test_manifest_records_content_hashes() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "src/main.rs", "fn main() {}\n");
+    let out = TempDir::new().unwrap();
+    let manifest = out.path().join("manifest.json");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--manifest",
+        manifest.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    let files = json["files"].as_array().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0]["path"], "src/main.rs");
+    // Same digest as `sha256sum src/main.rs`.
+    assert_eq!(files[0]["hash"], "536e506bb90914c243a12b397b9a998f85ae2cbd9ba02dfd03a9e155ca5ca0f4");
+    assert_eq!(files[0]["bytes"], 13);
+    assert_eq!(files[0]["lines"], 1);
+
+    // The size, like the hash, is of the bytes on disk, not the decoded text.
+    write_file(dir.path(), "wide.txt", [0xFF, 0xFE, b'h', 0, b'i', 0, b'\n', 0]);
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--manifest",
+        manifest.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    assert_eq!(json["files"][1]["path"], "wide.txt");
+    assert_eq!(json["files"][1]["bytes"], 8);
+    assert_eq!(json["files"][1]["lines"], 1);
+
+    // Diff modes print no files, so a manifest would always be empty.
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--git-to",
+        "HEAD",
+        "--manifest",
+        manifest.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn test_since_manifest_only_dumps_changed_files() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "stable.rs", "fn stable() {}\n");
+    write_file(dir.path(), "edited.rs", "fn before() {}\n");
+    let out = TempDir::new().unwrap();
+    let manifest = out.path().join("manifest.json");
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--manifest", manifest.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    write_file(dir.path(), "edited.rs", "fn after() {}\n");
+    write_file(dir.path(), "added.rs", "fn added() {}\n");
+    let output = run_repo_walker(&["--path", root, "--since-manifest", manifest.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("fn after() {}"));
+    assert!(stdout.contains("fn added() {}"));
+    assert!(!stdout.contains("stable.rs"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unchanged: 1"));
+}
+
+#[test]
+fn test_since_manifest_refreshes_manifest_in_place() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "stable.rs", "fn stable() {}\n");
+    write_file(dir.path(), "edited.rs", "fn before() {}\n");
+    let out = TempDir::new().unwrap();
+    let manifest = out.path().join("manifest.json");
+    let manifest = manifest.to_str().unwrap();
+    let root = dir.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--manifest", manifest]);
+    assert!(output.status.success());
+
+    write_file(dir.path(), "edited.rs", "fn after() {}\n");
+    let output = run_repo_walker(&[
+        "--path",
+        root,
+        "--since-manifest",
+        manifest,
+        "--manifest",
+        manifest,
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("fn after() {}"));
+    assert!(!stdout.contains("stable.rs"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(manifest).unwrap()).unwrap();
+    let paths: Vec<&str> = json["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["edited.rs", "stable.rs"]);
+
+    let output = run_repo_walker(&["--path", root, "--since-manifest", manifest]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("### File:"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unchanged: 2"));
+}
+
+#[test]
+fn test_summary_json_sidecar() {
+    let sample = sample_repo();
+    let root = sample.path().to_str().unwrap();
+    let out = TempDir::new().unwrap();
+    let summary = out.path().join("summary.json");
+    let summary = summary.to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--extensions", "rs", "--summary-json", summary]);
+    assert!(output.status.success());
+    // The human-readable listing still goes to stdout.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("### File: src/lib.rs"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(summary).unwrap()).unwrap();
+    assert_eq!(json["total_files"], 2);
+    assert_eq!(json["total_lines"], 6);
+    assert_eq!(json["files"].as_array().unwrap().len(), 2);
+    assert_eq!(json["directories"]["src"]["files"], 2);
+    assert_eq!(json["filters"]["extensions"][0], "rs");
+    assert!(json.get("diff_stat").is_none());
+
+    let output = run_repo_walker(&["--path", root, "--commit", "HEAD", "--summary-json", summary]);
+    assert!(output.status.success());
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(summary).unwrap()).unwrap();
+    assert_eq!(json["total_files"], 0);
+    assert_eq!(json["diff_stat"]["files_changed"], 2);
+}
+
+#[test]
+fn test_summary_json_counts_the_printed_portion() {
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "a.txt", "one\n\n\ntwo\nthree\n");
+    write_file(dir.path(), "b.txt", "\nfour\n");
+    let out = TempDir::new().unwrap();
+    let summary = out.path().join("summary.json");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--head",
+        "2",
+        "--ignore-blank-lines",
+        "--stats-only",
+        "--summary-json",
+        summary.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Total: 2 files, 3 lines, 13 B"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&summary).unwrap()).unwrap();
+    assert_eq!(json["total_lines"], 3);
+    assert_eq!(json["total_bytes"], 13);
+    assert_eq!(json["files"][0]["lines"], 2);
+    assert_eq!(json["files"][0]["bytes"], 8);
+}
+
+#[test]
+fn test_gzip_output() {
+    use std::io::Read;
+
+    let dir = TempDir::new().unwrap();
+    write_file(dir.path(), "lib.rs", "pub fn compressed() {}\n");
+    let out = TempDir::new().unwrap();
+    let archive = out.path().join("out.txt.gz");
+
+    let output = run_repo_walker(&[
+        "--path",
+        dir.path().to_str().unwrap(),
+        "--output",
+        archive.to_str().unwrap(),
+        "--gzip",
+    ]);
+    assert!(output.status.success());
+
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(&archive).unwrap())
+        .read_to_string(&mut text)
+        .unwrap();
+    assert!(text.starts_with("### File: lib.rs"));
+    assert!(text.contains("pub fn compressed() {}"));
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--gzip"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_include_untracked_in_git_diff() {
+    let sample = sample_repo();
+    write_file(sample.path(), ".gitignore", "*.log\n");
+    commit_all(sample.path(), "ignore logs");
+    write_file(sample.path(), "src/new.rs", "pub fn untracked() {}\n");
+    write_file(sample.path(), "debug.log", "ignored\n");
+    let root = sample.path().to_str().unwrap();
+
+    let output = run_repo_walker(&["--path", root, "--git-to", "WORKTREE"]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("untracked"));
+
+    let output = run_repo_walker(&[
+        "--path",
+        root,
+        "--git-to",
+        "WORKTREE",
+        "--include-untracked",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+pub fn untracked() {}"));
+    assert!(!stdout.contains("ignored"));
+    assert!(stdout.contains("1 file changed, 1 insertion(+), 0 deletions(-)"));
+
+    let output = run_repo_walker(&[
+        "--path",
+        root,
+        "--git-to",
+        "WORKTREE",
+        "--include-untracked",
+        "--extensions",
+        "md",
+    ]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("untracked"));
+
+    write_file(sample.path(), "big.txt", "x".repeat(4096));
+    let output = run_repo_walker(&[
+        "--path",
+        root,
+        "--git-to",
+        "WORKTREE",
+        "--include-untracked",
+        "--max-file-size",
+        "1024",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+pub fn untracked() {}"));
+    assert!(!stdout.contains("big.txt"));
+    assert!(!stdout.contains("+xxxx"));
+    assert!(stdout.contains("1 file changed, 1 insertion(+), 0 deletions(-)"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipping large file (4096 bytes): big.txt"));
+
+    // Untracked files are never written to the object database.
+    let output = std::process::Command::new("git")
+        .args(["hash-object", "src/new.rs"])
+        .current_dir(sample.path())
+        .output()
+        .unwrap();
+    let blob_id = String::from_utf8(output.stdout).unwrap().trim().to_string();
+    let status = std::process::Command::new("git")
+        .args(["cat-file", "-e", &blob_id])
+        .current_dir(sample.path())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+fn test_wrap_long_lines() {
+    let dir = TempDir::new().unwrap();
+    let long_line = "x".repeat(200);
+    write_file(dir.path(), "wide.txt", format!("short\n{}\n", long_line));
+
+    let output = run_repo_walker(&["--path", dir.path().to_str().unwrap(), "--wrap", "80"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // Counts describe the file, not the wrapped output.
+    assert!(stdout.contains("### File: wide.txt (2 lines, 207 B)"));
+    let pieces: Vec<&str> = stdout.lines().filter(|l| l.starts_with('x')).collect();
+    assert_eq!(pieces, vec![&long_line[..80], &long_line[..80], &long_line[..40]]);
+    assert!(stdout.contains("short\n"));
+}